@@ -0,0 +1,155 @@
+//! Caret style rendering of format string parse errors.
+
+use ::std::fmt::Display;
+
+use ::chumsky::error::Rich;
+
+use crate::display_bytes;
+
+/// Get the width of bytes when displayed using [display_bytes].
+fn display_width(bytes: &[u8]) -> usize {
+    display_bytes(bytes).to_string().chars().count()
+}
+
+/// An unbalanced brace of a format string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unbalanced {
+    /// A `{` at the start of range which is not closed by the end of range.
+    Open(usize, usize),
+    /// A `}` at index which was never opened.
+    Close(usize),
+}
+
+impl Unbalanced {
+    /// Find the first unbalanced brace of a format string.
+    fn find(fmt: &[u8]) -> Option<Self> {
+        let mut open = None;
+        let mut idx = 0;
+        while let Some(b) = fmt.get(idx) {
+            match (open, b, fmt.get(idx + 1)) {
+                (None, b'{', Some(b'{')) | (None, b'}', Some(b'}')) => idx += 1,
+                (None, b'{', _) => open = Some(idx),
+                (None, b'}', _) => return Some(Self::Close(idx)),
+                (Some(_), b'}', _) => open = None,
+                (Some(start), b'{', _) => return Some(Self::Open(start, idx)),
+                _ => {}
+            }
+            idx += 1;
+        }
+        open.map(|start| Self::Open(start, fmt.len()))
+    }
+}
+
+/// Parse errors of a format string, displayed with the format string and carets under the
+/// offending bytes.
+///
+/// Errors are expected to have spans relative to the start of the format string, as those
+/// returned by [parse_fmt][crate::parse_fmt] do. If the format string has an unbalanced brace
+/// it is shown instead of the errors, with a hint on how to escape it.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagnostic<'a, 'e> {
+    /// Format string errors occurred in.
+    fmt: &'a [u8],
+    /// Errors to display.
+    errors: &'a [Rich<'e, u8>],
+}
+
+impl<'a, 'e> Diagnostic<'a, 'e> {
+    /// Create a new diagnostic for the errors of a format string.
+    pub const fn new(fmt: &'a [u8], errors: &'a [Rich<'e, u8>]) -> Self {
+        Self { fmt, errors }
+    }
+
+    /// Write the format string with carets under a range of it.
+    ///
+    /// # Errors
+    /// If the formatter errors.
+    fn fmt_carets(
+        &self,
+        start: usize,
+        end: usize,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let fmt = self.fmt;
+        let start = start.min(fmt.len());
+        let end = end.clamp(start, fmt.len());
+        let pad = display_width(&fmt[..start]);
+        let carets = display_width(&fmt[start..end]).max(1);
+        writeln!(f, "  | {}", display_bytes(fmt))?;
+        write!(f, "  | {:pad$}{:^<carets$}", "", "")
+    }
+}
+
+impl Display for Diagnostic<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match Unbalanced::find(self.fmt) {
+            Some(Unbalanced::Open(start, end)) => {
+                writeln!(f, "unclosed `{{`")?;
+                self.fmt_carets(start, end, f)?;
+                return write!(f, "\n  = hint: use `{{{{` to write a literal `{{`");
+            }
+            Some(Unbalanced::Close(idx)) => {
+                writeln!(f, "unmatched `}}`")?;
+                self.fmt_carets(idx, idx + 1, f)?;
+                return write!(f, "\n  = hint: use `}}}}` to write a literal `}}`");
+            }
+            None => {}
+        }
+
+        match self.errors {
+            [] => write!(f, "unknown parse error"),
+            [head @ .., tail] => {
+                for err in head {
+                    writeln!(f, "{err}")?;
+                    self.fmt_carets(err.span().start, err.span().end, f)?;
+                    writeln!(f)?;
+                }
+                writeln!(f, "{tail}")?;
+                self.fmt_carets(tail.span().start, tail.span().end, f)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::parse_fmt;
+
+    fn render(fmt: &[u8]) -> String {
+        parse_fmt(fmt)
+            .find_map(Result::err)
+            .map(|errors| Diagnostic::new(fmt, &errors).to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn unmatched() {
+        assert_eq!(
+            render(b"echo a}b"),
+            "unmatched `}`\n  | echo a}b\n  |       ^\n  = hint: use `}}` to write a literal `}`",
+        );
+    }
+
+    #[test]
+    fn unclosed() {
+        assert_eq!(
+            render(b"{{x}} {1"),
+            "unclosed `{`\n  | {{x}} {1\n  |       ^^\n  = hint: use `{{` to write a literal `{`",
+        );
+        assert_eq!(
+            render(b"{a{b}"),
+            "unclosed `{`\n  | {a{b}\n  | ^^\n  = hint: use `{{` to write a literal `{`",
+        );
+    }
+
+    #[test]
+    fn non_utf8() {
+        assert_eq!(
+            render(b"\xFF\n}"),
+            "unmatched `}`\n  | \\xFF\\n}\n  |       ^\n  = hint: use `}}` to write a literal `}`",
+        );
+    }
+}
@@ -8,7 +8,12 @@ use ::std::{
 
 use ::chumsky::{Parser, error::Rich, extra::ParserExtra};
 
+use crate::spec::Spec;
+
+pub mod diagnostic;
 pub mod lookup;
+pub mod spec;
+pub mod template;
 
 /// Get a debug implementor for a byte array representing text.
 pub fn debug_bytes<'a>(bytes: &'a [u8]) -> impl 'a + Debug {
@@ -71,12 +76,20 @@ impl Debug for Chunk<'_> {
 
 impl<'a> Chunk<'a> {
     /// Resolve the chunk returning either the text as is, or the result of a lookup.
+    ///
+    /// Any [Spec] following the key of a lookup is applied to the result.
+    ///
+    /// # Errors
+    /// If the lookup fails, and the spec has no condition.
     pub fn resolve<E>(
         self,
         lookup: impl FnOnce(&'a [u8]) -> Result<Cow<'a, [u8]>, E>,
     ) -> Result<Cow<'a, [u8]>, E> {
         match self {
-            Chunk::Lookup(items) => lookup(items),
+            Chunk::Lookup(items) => {
+                let (key, spec) = Spec::split(items);
+                spec.into_resolved(lookup(key))
+            }
             Chunk::Text(items) => Ok(Cow::Borrowed(items)),
         }
     }
@@ -112,6 +125,7 @@ pub fn parse_fmt<'a>(
     use ::chumsky::prelude::*;
     let parser =
         Chunk::parser::<::chumsky::extra::Err<Rich<u8>>>().then(any().repeated().to_slice());
+    let len = fmt.len();
     let mut fmt = fmt;
     ::std::iter::from_fn(move || {
         if fmt.is_empty() {
@@ -122,11 +136,32 @@ pub fn parse_fmt<'a>(
                 fmt = remainder;
                 Some(Ok(chunk))
             }
-            Err(err) => Some(Err(err)),
+            Err(err) => {
+                let offset = len - fmt.len();
+                Some(Err(err
+                    .into_iter()
+                    .map(|err| offset_error(err, offset))
+                    .collect()))
+            }
         }
     })
 }
 
+/// Offset the span of an error, such that it is relative to the start of the format string
+/// instead of the start of the chunk.
+fn offset_error(err: Rich<'_, u8>, offset: usize) -> Rich<'_, u8> {
+    use ::chumsky::{error::RichReason, label::LabelError, span::SimpleSpan};
+
+    let span = err.span();
+    let span = SimpleSpan::from(span.start + offset..span.end + offset);
+    match err.into_reason() {
+        RichReason::ExpectedFound { expected, found } => {
+            <Rich<u8> as LabelError<&[u8], _>>::expected_found(expected, found, span)
+        }
+        RichReason::Custom(msg) => Rich::custom(span, msg),
+    }
+}
+
 /// Use an iterator of chunks to write formatted bytes to a container.
 pub fn format_to<'a, C, I, E, F>(buf: &mut C, chunks: I, mut lookup: F) -> Result<(), E>
 where
@@ -220,7 +255,7 @@ impl<'a, E> FormatError<'a, E> {
 mod tests {
     use ::std::collections::HashMap;
 
-    use crate::lookup::SeqLookupError;
+    use crate::lookup::{EnvLookupError, PathLookupError, SeqLookupError, TimeLookupError};
 
     use super::*;
     use ::chumsky::{IterParser, extra};
@@ -330,6 +365,16 @@ mod tests {
             ))
         );
 
+        assert_eq!(
+            format(b"{:0>5}|{1:upper:^7}|{-1:.2}", lookup::seq(&seq)),
+            Ok(Vec::from(b"0ZERO|  ONE  |TW"))
+        );
+
+        assert_eq!(
+            format(b"{HOME:+home}, {USER:-nobody}", lookup::seq_map(&values)),
+            Ok(Vec::from(b"home, nobody"))
+        );
+
         assert_eq!(
             format::<Vec<u8>, _, _>(
                 b"0: {}, 1: {}, 2: {}, 1: {1}, 0: {0}, last: {-1}, next: {}",
@@ -338,4 +383,126 @@ mod tests {
             Err(FormatError::Lookup(SeqLookupError::OutOfRange(3, -3..3)))
         );
     }
+
+    #[test]
+    fn format_path() {
+        use super::format;
+        let path = "/var/home/user/notes.tar.gz";
+
+        assert_eq!(
+            format(
+                b"{path}|{name}|{stem}|{ext}|{parent}|{parent[2]}|{parent[0]}",
+                lookup::path(path)
+            ),
+            Ok(Vec::from(
+                b"/var/home/user/notes.tar.gz|notes.tar.gz|notes.tar|gz|/var/home/user|/var/home|/var/home/user/notes.tar.gz"
+            ))
+        );
+
+        assert_eq!(
+            format(
+                b"{component[0]}|{component[1]}|{component[-2]}",
+                lookup::path(path)
+            ),
+            Ok(Vec::from(b"/|var|user"))
+        );
+
+        assert_eq!(
+            format(b"{stem}.{ext:-txt}", lookup::path("notes")),
+            Ok(Vec::from(b"notes.txt"))
+        );
+
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{component[5]}", lookup::path(path)),
+            Err(FormatError::Lookup(PathLookupError::Missing(
+                b"component[5]"
+            )))
+        );
+
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{parent[x]}", lookup::path(path)),
+            Err(FormatError::Lookup(PathLookupError::ParseIndex(
+                b"parent[x]"
+            )))
+        );
+
+        let values: [(&[u8], &[u8]); 1] = [(b"dest", b"/tmp")];
+        assert_eq!(
+            format(
+                b"{dest}/{name}",
+                lookup::either(lookup::path(path), lookup::seq_map(&values))
+            ),
+            Ok(Vec::from(b"/tmp/notes.tar.gz"))
+        );
+    }
+
+    #[test]
+    fn format_env_time() {
+        use super::format;
+        use ::jiff::{Zoned, tz::TimeZone};
+
+        let path = ::std::env::var_os("PATH").unwrap_or_default();
+        assert_eq!(
+            format(b"{env:PATH}", lookup::env()),
+            Ok(path.into_encoded_bytes())
+        );
+        assert_eq!(
+            format(b"{env:FILE_SUITE_UNSET:-unset}", lookup::env()),
+            Ok(Vec::from(b"unset"))
+        );
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{PATH}", lookup::env()),
+            Err(FormatError::Lookup(EnvLookupError::UnknownKey(b"PATH")))
+        );
+
+        let year = Zoned::now().strftime("%Y").to_string();
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{now:%Y}", lookup::now()).ok(),
+            Some(year.into_bytes())
+        );
+
+        let hour = Zoned::now().strftime("%H:00").to_string();
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{now:%H:00|}", lookup::now()).ok(),
+            Some(hour.into_bytes())
+        );
+        let hour = Zoned::now().strftime("%H:00").to_string();
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{now:%H:00|>7}", lookup::now()).ok(),
+            Some(format!("  {hour}").into_bytes())
+        );
+        let secs = format::<Vec<u8>, _, _>(b"{now:+%s|}", lookup::now())
+            .ok()
+            .and_then(|secs| String::from_utf8(secs).ok())
+            .and_then(|secs| secs.strip_prefix('+')?.parse::<i64>().ok());
+        assert_eq!(
+            secs.map(|secs| secs.abs_diff(Zoned::now().timestamp().as_second()) < 60),
+            Some(true)
+        );
+
+        let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let year = ::std::fs::metadata(manifest)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|mtime| ::jiff::Timestamp::try_from(mtime).ok())
+            .map(|mtime| {
+                format!(
+                    "{}/Cargo.toml",
+                    mtime.to_zoned(TimeZone::system()).strftime("%Y")
+                )
+            });
+        assert_eq!(
+            format::<Vec<u8>, _, _>(
+                b"{mtime:%Y}/{name}",
+                lookup::either(lookup::mtime(manifest), lookup::path(manifest))
+            )
+            .ok(),
+            year.map(String::into_bytes)
+        );
+        assert_eq!(
+            format::<Vec<u8>, _, _>(b"{mtime:%Y}", lookup::mtime("/nonexistent"))
+                .map_err(|err| matches!(err, FormatError::Lookup(TimeLookupError::Mtime(..)))),
+            Err(true)
+        );
+    }
 }
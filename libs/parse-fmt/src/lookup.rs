@@ -5,8 +5,12 @@ use ::std::{
     collections::{BTreeMap, HashMap},
     hash::{BuildHasher, Hash},
     ops::Range,
+    path::Path,
+    str::Utf8Error,
 };
 
+use ::jiff::{Timestamp, Zoned, tz::TimeZone};
+
 use crate::display_bytes;
 
 /// Create a lookup function combining multiple other lookup functions and returning
@@ -143,3 +147,206 @@ where
             .map(|value| Cow::Borrowed(value.as_ref()))
     }
 }
+
+/// Error returned when path lookup fails.
+#[derive(Debug, ::thiserror::Error, PartialEq, Eq, Hash)]
+pub enum PathLookupError<'a> {
+    /// Key is not one of the keys provided.
+    #[error("unknown path key `{}`", display_bytes(.0))]
+    UnknownKey(&'a [u8]),
+    /// Could not parse index of key.
+    #[error("could not parse index of path key `{}`", display_bytes(.0))]
+    ParseIndex(&'a [u8]),
+    /// Path does not have the requested part.
+    #[error("path has no part `{}`", display_bytes(.0))]
+    Missing(&'a [u8]),
+}
+
+/// Parse the index of a key of the form `name[N]`, returning `None` if key is not of that form.
+///
+/// # Errors
+/// If the brackets do not contain an integer.
+fn path_key_index<'a>(key: &'a [u8], name: &[u8]) -> Option<Result<isize, PathLookupError<'a>>> {
+    let index = key
+        .strip_prefix(name)?
+        .strip_prefix(b"[")?
+        .strip_suffix(b"]")?;
+    Some(
+        str::from_utf8(index)
+            .ok()
+            .and_then(|index| index.parse().ok())
+            .ok_or(PathLookupError::ParseIndex(key)),
+    )
+}
+
+/// Create a lookup function resolving parts of a path, without any utf-8 conversion.
+///
+/// The available keys are:
+/// - `path`, the whole path.
+/// - `name`, the final component of the path.
+/// - `stem`, the name without it's extension.
+/// - `ext`, the extension of the name.
+/// - `parent`, the path without it's final component, same as `parent[1]`.
+/// - `parent[N]`, the path with N components removed from the end, `parent[0]` being the path.
+/// - `component[N]`, the Nth component of the path, negative values counting from the end.
+///
+/// Parts which the path does not have, such as the extension of `file`, fail to look up, so
+/// `{ext:-}` may be used to have them be empty.
+pub fn path<'a, P>(
+    path: &'a P,
+) -> impl 'a + Fn(&'a [u8]) -> Result<Cow<'a, [u8]>, PathLookupError<'a>>
+where
+    P: ?Sized + AsRef<Path>,
+{
+    let path = path.as_ref();
+    move |key| {
+        let part = match key {
+            b"path" => Some(path.as_os_str()),
+            b"name" => path.file_name(),
+            b"stem" => path.file_stem(),
+            b"ext" => path.extension(),
+            b"parent" => path.parent().map(Path::as_os_str),
+            _ => {
+                if let Some(index) = path_key_index(key, b"parent") {
+                    usize::try_from(index?)
+                        .ok()
+                        .and_then(|index| path.ancestors().nth(index))
+                        .map(Path::as_os_str)
+                } else if let Some(index) = path_key_index(key, b"component") {
+                    let index = index?;
+                    let index = if index < 0 {
+                        path.components().count().checked_sub(index.unsigned_abs())
+                    } else {
+                        Some(index.unsigned_abs())
+                    };
+                    index
+                        .and_then(|index| path.components().nth(index))
+                        .map(|component| component.as_os_str())
+                } else {
+                    return Err(PathLookupError::UnknownKey(key));
+                }
+            }
+        };
+
+        part.map(|part| Cow::Borrowed(part.as_encoded_bytes()))
+            .ok_or(PathLookupError::Missing(key))
+    }
+}
+
+/// Error returned when environment lookup fails.
+#[derive(Debug, ::thiserror::Error, PartialEq, Eq)]
+pub enum EnvLookupError<'a> {
+    /// Key is not of the form `env:NAME`.
+    #[error("unknown environment key `{}`", display_bytes(.0))]
+    UnknownKey(&'a [u8]),
+    /// Name of variable is not utf-8.
+    #[error("environment variable name `{}` is not utf-8: {}", display_bytes(.1), .0)]
+    NameNotUtf8(#[source] Utf8Error, &'a [u8]),
+    /// Variable is not set.
+    #[error("environment variable `{0}` is not set")]
+    Missing(&'a str),
+}
+
+/// Create a lookup function resolving environment variables using keys of the form `env:NAME`.
+///
+/// Values are used as is, without any utf-8 conversion.
+pub fn env<'a>() -> impl 'a + Fn(&'a [u8]) -> Result<Cow<'a, [u8]>, EnvLookupError<'a>> {
+    |key| {
+        let name = key
+            .strip_prefix(b"env:")
+            .ok_or(EnvLookupError::UnknownKey(key))?;
+        let name = str::from_utf8(name).map_err(|err| EnvLookupError::NameNotUtf8(err, name))?;
+        ::std::env::var_os(name)
+            .map(|value| Cow::Owned(value.into_encoded_bytes()))
+            .ok_or(EnvLookupError::Missing(name))
+    }
+}
+
+/// Error returned when time lookup fails.
+#[derive(Debug, ::thiserror::Error)]
+pub enum TimeLookupError<'a> {
+    /// Key is not one of the keys provided.
+    #[error("unknown time key `{}`", display_bytes(.0))]
+    UnknownKey(&'a [u8]),
+    /// Format of key is not utf-8.
+    #[error("time format `{}` is not utf-8: {}", display_bytes(.1), .0)]
+    FormatNotUtf8(#[source] Utf8Error, &'a [u8]),
+    /// Time could not be formatted.
+    #[error("could not format time using `{1}`, {0}")]
+    Format(#[source] ::jiff::Error, &'a str),
+    /// Modification time of file could not be read.
+    #[error("could not get modification time of `{1}`, {0}")]
+    Mtime(#[source] ::std::io::Error, ::std::path::Display<'a>),
+    /// Modification time of file is out of range.
+    #[error("modification time of `{1}` is out of range, {0}")]
+    MtimeRange(#[source] ::jiff::Error, ::std::path::Display<'a>),
+}
+
+/// Default format of times, used when a time key has no format.
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Format time using the format of a key of the form `name` or `name:FORMAT`, returning `None`
+/// if key does not have the given name.
+///
+/// # Errors
+/// If the format is not utf-8 or the time cannot be formatted with it.
+fn format_time<'a>(
+    key: &'a [u8],
+    name: &[u8],
+    time: impl FnOnce() -> Result<Zoned, TimeLookupError<'a>>,
+) -> Option<Result<Cow<'a, [u8]>, TimeLookupError<'a>>> {
+    let format = match key.strip_prefix(name)? {
+        [] => DEFAULT_TIME_FORMAT,
+        [b':', format @ ..] => match str::from_utf8(format) {
+            Ok(format) => format,
+            Err(err) => return Some(Err(TimeLookupError::FormatNotUtf8(err, format))),
+        },
+        _ => return None,
+    };
+
+    Some(time().and_then(|time| {
+        ::jiff::fmt::strtime::format(format, &time)
+            .map(|time| Cow::Owned(time.into_bytes()))
+            .map_err(|err| TimeLookupError::Format(err, format))
+    }))
+}
+
+/// Create a lookup function resolving the current time in the system time zone, using keys of
+/// the form `now:FORMAT` where FORMAT is a strftime-style format such as `%Y-%m-%d`.
+///
+/// The time is taken when the lookup function is created, such that all lookups agree on it. If
+/// the format is left out, as in `now`, the time is formatted as `2025-01-31T12:30:00+01:00`.
+/// Formats containing a colon followed by something resembling modifiers, such as `%H:00`, should
+/// end the key with a `|`, as in `{now:%H:00|}`.
+pub fn now<'a>() -> impl 'a + Fn(&'a [u8]) -> Result<Cow<'a, [u8]>, TimeLookupError<'a>> {
+    let now = Zoned::now();
+    move |key| {
+        format_time(key, b"now", || Ok(now.clone()))
+            .unwrap_or(Err(TimeLookupError::UnknownKey(key)))
+    }
+}
+
+/// Create a lookup function resolving the modification time of a file in the system time zone,
+/// using keys of the form `mtime:FORMAT`, formatted as for [now].
+///
+/// The modification time is read when looked up.
+pub fn mtime<'a, P>(
+    path: &'a P,
+) -> impl 'a + Fn(&'a [u8]) -> Result<Cow<'a, [u8]>, TimeLookupError<'a>>
+where
+    P: ?Sized + AsRef<Path>,
+{
+    let path = path.as_ref();
+    move |key| {
+        format_time(key, b"mtime", || {
+            let mtime = path
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .map_err(|err| TimeLookupError::Mtime(err, path.display()))?;
+            Timestamp::try_from(mtime)
+                .map(|mtime| mtime.to_zoned(TimeZone::system()))
+                .map_err(|err| TimeLookupError::MtimeRange(err, path.display()))
+        })
+        .unwrap_or(Err(TimeLookupError::UnknownKey(key)))
+    }
+}
@@ -0,0 +1,506 @@
+//! Format specifications applied to the results of lookups.
+//!
+//! A lookup may be followed by any amount of colon separated modifiers, `{key:mod:mod}`, which
+//! are applied in order to the value. A modifier is either a case conversion, `upper` or `lower`,
+//! or a format spec, `[[fill]align][0][width][.max]`, similar to the one used by [format!], with
+//! width and max limited to [MAX_WIDTH].
+//!
+//! The last modifier may also be a shell-style condition, `-text` to use text if the lookup fails
+//! or is empty, or `+text` to use text if the lookup succeeds and is not empty, and the empty
+//! string otherwise. The text extends to the end of the lookup and is passed through any other
+//! modifiers.
+//!
+//! Since keys may contain colons, the key ends at the first colon for which the remainder is a
+//! valid list of modifiers. A key containing something which would be taken for modifiers, such
+//! as the time format of `{now:%H:00}`, may instead be ended explicitly by a `|`, which is
+//! followed by any modifiers, as in `{now:%H:00|>8}` or `{now:%H:00|}`.
+
+use ::std::borrow::Cow;
+
+/// Alignment of padded values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Align {
+    /// Pad on the right, `<`.
+    Left,
+    /// Pad on both sides, `^`, with any uneven padding on the right.
+    Center,
+    /// Pad on the left, `>`.
+    Right,
+}
+
+impl Align {
+    /// Get alignment from a byte.
+    const fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            b'<' => Some(Self::Left),
+            b'^' => Some(Self::Center),
+            b'>' => Some(Self::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Case conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// Convert to uppercase, `upper`.
+    Upper,
+    /// Convert to lowercase, `lower`.
+    Lower,
+}
+
+/// Transformation applied to a looked up value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transform {
+    /// Pad value to at least width characters using fill.
+    Pad {
+        /// Character to pad with.
+        fill: char,
+        /// Where to put value in padding.
+        align: Align,
+        /// Minimum amount of characters.
+        width: usize,
+    },
+    /// Truncate value to at most the given amount of characters.
+    Truncate(usize),
+    /// Convert case of value.
+    Case(Case),
+}
+
+/// Visit the characters of bytes, invalid utf-8 bytes are visited one at a time as `None`.
+fn for_each_char(bytes: &[u8], mut f: impl FnMut(Option<char>, &[u8])) {
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        for (idx, chr) in valid.char_indices() {
+            f(Some(chr), &valid.as_bytes()[idx..idx + chr.len_utf8()]);
+        }
+        for b in chunk.invalid() {
+            f(None, ::std::slice::from_ref(b));
+        }
+    }
+}
+
+/// Count characters of bytes, counting invalid utf-8 bytes as one character each.
+fn char_count(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    for_each_char(bytes, |_, _| count += 1);
+    count
+}
+
+impl Transform {
+    /// Apply transform to a value.
+    pub fn apply<'a>(self, value: Cow<'a, [u8]>) -> Cow<'a, [u8]> {
+        match self {
+            Transform::Pad { fill, align, width } => {
+                let Some(padding) = width.checked_sub(char_count(&value)).filter(|p| *p > 0) else {
+                    return value;
+                };
+                let (left, right) = match align {
+                    Align::Left => (0, padding),
+                    Align::Center => (padding / 2, padding - padding / 2),
+                    Align::Right => (padding, 0),
+                };
+                let mut fill_buf = [0u8; 4];
+                let fill = fill.encode_utf8(&mut fill_buf).as_bytes();
+
+                let mut padded = Vec::with_capacity(
+                    padding
+                        .checked_mul(fill.len())
+                        .and_then(|len| len.checked_add(value.len()))
+                        .unwrap_or(value.len()),
+                );
+                for _ in 0..left {
+                    padded.extend_from_slice(fill);
+                }
+                padded.extend_from_slice(&value);
+                for _ in 0..right {
+                    padded.extend_from_slice(fill);
+                }
+                Cow::Owned(padded)
+            }
+            Transform::Truncate(max) => {
+                let mut count = 0;
+                let mut len = 0;
+                for_each_char(&value, |_, bytes| {
+                    if count < max {
+                        count += 1;
+                        len += bytes.len();
+                    }
+                });
+                match value {
+                    Cow::Borrowed(bytes) => Cow::Borrowed(&bytes[..len]),
+                    Cow::Owned(mut bytes) => {
+                        bytes.truncate(len);
+                        Cow::Owned(bytes)
+                    }
+                }
+            }
+            Transform::Case(case) => {
+                let mut converted = Vec::with_capacity(value.len());
+                let mut buf = [0u8; 4];
+                for_each_char(&value, |chr, bytes| match chr {
+                    Some(chr) if case == Case::Upper => {
+                        for chr in chr.to_uppercase() {
+                            converted.extend_from_slice(chr.encode_utf8(&mut buf).as_bytes());
+                        }
+                    }
+                    Some(chr) => {
+                        for chr in chr.to_lowercase() {
+                            converted.extend_from_slice(chr.encode_utf8(&mut buf).as_bytes());
+                        }
+                    }
+                    None => converted.extend_from_slice(bytes),
+                });
+                Cow::Owned(converted)
+            }
+        }
+    }
+}
+
+/// Largest width or max of a format spec, the same limit as used by [format!].
+pub const MAX_WIDTH: usize = u16::MAX as usize;
+
+/// Parse an unsigned integer no larger than [MAX_WIDTH] from the start of input.
+///
+/// Returns `None` if there are digits which do not form such an integer.
+fn parse_int(input: &[u8]) -> Option<(Option<usize>, &[u8])> {
+    let len = input.iter().take_while(|b| b.is_ascii_digit()).count();
+    let (digits, rest) = input.split_at(len);
+    if digits.is_empty() {
+        return Some((None, rest));
+    }
+    let value = str::from_utf8(digits)
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|value| *value <= MAX_WIDTH)?;
+    Some((Some(value), rest))
+}
+
+/// Parse a format spec, `[[fill]align][0][width][.max]`, from the start of input.
+fn parse_format(input: &[u8], transforms: &mut Vec<Transform>) -> Option<usize> {
+    let first_len = input
+        .utf8_chunks()
+        .next()
+        .and_then(|chunk| chunk.valid().chars().next())
+        .map_or(0, char::len_utf8);
+
+    let (fill, align, rest) = if let Some(align) = input
+        .get(first_len)
+        .copied()
+        .and_then(Align::from_byte)
+        .filter(|_| first_len > 0)
+    {
+        let fill = str::from_utf8(&input[..first_len]).ok()?.chars().next()?;
+        (fill, Some(align), &input[first_len + 1..])
+    } else if let Some(align) = input.first().copied().and_then(Align::from_byte) {
+        (' ', Some(align), &input[1..])
+    } else {
+        (' ', None, input)
+    };
+
+    let (fill, align, rest) = match (align, rest) {
+        (None, [b'0', rest @ ..]) => ('0', Align::Right, rest),
+        (align, rest) => (fill, align.unwrap_or(Align::Left), rest),
+    };
+
+    let (width, rest) = parse_int(rest)?;
+
+    let (max, rest) = match rest {
+        [b'.', rest @ ..] => match parse_int(rest)? {
+            (Some(max), rest) => (Some(max), rest),
+            (None, _) => return None,
+        },
+        rest => (None, rest),
+    };
+
+    if let Some(max) = max {
+        transforms.push(Transform::Truncate(max));
+    }
+    if let Some(width) = width {
+        transforms.push(Transform::Pad { fill, align, width });
+    }
+
+    Some(input.len() - rest.len())
+}
+
+/// Condition deciding the value of a lookup depending on it's result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Cond<'a> {
+    /// Use text if lookup fails or is empty, `-text`.
+    Default(Cow<'a, [u8]>),
+    /// Use text if lookup succeeds and is not empty, `+text`.
+    Alternate(Cow<'a, [u8]>),
+}
+
+impl Cond<'_> {
+    /// Borrow the text of condition.
+    fn borrow(&self) -> Cond<'_> {
+        match self {
+            Cond::Default(text) => Cond::Default(Cow::Borrowed(text)),
+            Cond::Alternate(text) => Cond::Alternate(Cow::Borrowed(text)),
+        }
+    }
+
+    /// Convert into a condition owning it's text.
+    fn into_owned(self) -> Cond<'static> {
+        match self {
+            Cond::Default(text) => Cond::Default(Cow::Owned(text.into_owned())),
+            Cond::Alternate(text) => Cond::Alternate(Cow::Owned(text.into_owned())),
+        }
+    }
+}
+
+/// Modifiers of a lookup, applied after the lookup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Spec<'a> {
+    /// Transforms to apply in order.
+    transforms: Vec<Transform>,
+    /// Condition on result of lookup.
+    cond: Option<Cond<'a>>,
+}
+
+impl<'a> Spec<'a> {
+    /// Parse a single modifier from the start of input, returning the amount of bytes used.
+    fn parse_modifier(&mut self, input: &'a [u8]) -> Option<usize> {
+        match input {
+            [b'-', text @ ..] => {
+                self.cond = Some(Cond::Default(Cow::Borrowed(text)));
+                return Some(input.len());
+            }
+            [b'+', text @ ..] => {
+                self.cond = Some(Cond::Alternate(Cow::Borrowed(text)));
+                return Some(input.len());
+            }
+            _ => {}
+        }
+
+        for (word, case) in [(b"upper", Case::Upper), (b"lower", Case::Lower)] {
+            if let Some(rest) = input.strip_prefix(word)
+                && matches!(rest, [] | [b':', ..])
+            {
+                self.transforms.push(Transform::Case(case));
+                return Some(word.len());
+            }
+        }
+
+        parse_format(input, &mut self.transforms)
+    }
+
+    /// Parse a colon separated list of modifiers.
+    ///
+    /// Returns `None` if the modifiers are invalid.
+    pub fn parse(mut modifiers: &'a [u8]) -> Option<Self> {
+        let mut spec = Self::default();
+        loop {
+            let len = spec.parse_modifier(modifiers)?;
+            match &modifiers[len..] {
+                [] => return Some(spec),
+                [b':', rest @ ..] => modifiers = rest,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Split the contents of a lookup into a key and a spec.
+    ///
+    /// If there is a `|` followed by valid modifiers the key ends at the first such `|`,
+    /// otherwise it ends at the first colon followed by valid modifiers. If there is no such
+    /// colon the whole lookup is the key.
+    pub fn split(lookup: &'a [u8]) -> (&'a [u8], Self) {
+        let split_at = |sep| {
+            move |(idx, b): (usize, &u8)| {
+                (*b == sep).then_some(())?;
+                Some((&lookup[..idx], Self::parse(&lookup[idx + 1..])?))
+            }
+        };
+        lookup
+            .iter()
+            .enumerate()
+            .find_map(split_at(b'|'))
+            .or_else(|| lookup.iter().enumerate().find_map(split_at(b':')))
+            .unwrap_or((lookup, Self::default()))
+    }
+
+    /// Get the transforms of spec.
+    pub fn transforms(&self) -> &[Transform] {
+        &self.transforms
+    }
+
+    /// Get the condition of spec.
+    pub const fn cond(&self) -> Option<&Cond<'a>> {
+        self.cond.as_ref()
+    }
+
+    /// Convert into a spec owning the text of it's condition.
+    pub fn into_owned(self) -> Spec<'static> {
+        let Self { transforms, cond } = self;
+        Spec {
+            transforms,
+            cond: cond.map(Cond::into_owned),
+        }
+    }
+
+    /// Apply transforms of spec to a value.
+    pub fn apply<'v>(&self, value: Cow<'v, [u8]>) -> Cow<'v, [u8]> {
+        self.transforms
+            .iter()
+            .fold(value, |value, transform| transform.apply(value))
+    }
+
+    /// Resolve the result of a lookup using the condition of spec, and apply transforms.
+    ///
+    /// # Errors
+    /// If the lookup failed and there is no condition.
+    pub fn resolve<'v, E>(&'v self, value: Result<Cow<'v, [u8]>, E>) -> Result<Cow<'v, [u8]>, E> {
+        self.resolve_with(self.cond.as_ref().map(Cond::borrow), value)
+    }
+
+    /// Resolve the result of a lookup like [Spec::resolve], consuming the spec such that the
+    /// result may borrow the text of it's condition for as long as the spec could.
+    ///
+    /// # Errors
+    /// If the lookup failed and there is no condition.
+    pub fn into_resolved<E>(mut self, value: Result<Cow<'a, [u8]>, E>) -> Result<Cow<'a, [u8]>, E> {
+        let cond = self.cond.take();
+        self.resolve_with(cond, value)
+    }
+
+    /// Resolve the result of a lookup using the given condition, and apply transforms.
+    ///
+    /// # Errors
+    /// If the lookup failed and there is no condition.
+    fn resolve_with<'v, E>(
+        &self,
+        cond: Option<Cond<'v>>,
+        value: Result<Cow<'v, [u8]>, E>,
+    ) -> Result<Cow<'v, [u8]>, E> {
+        let value = match (cond, value) {
+            (None, value) => value?,
+            (Some(Cond::Default(text)), Err(_)) => text,
+            (Some(Cond::Default(text)), Ok(value)) if value.is_empty() => text,
+            (Some(Cond::Default(_)), Ok(value)) => value,
+            (Some(Cond::Alternate(text)), Ok(value)) if !value.is_empty() => text,
+            (Some(Cond::Alternate(_)), _) => Cow::Borrowed(&[][..]),
+        };
+        Ok(self.apply(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::pretty_assertions::assert_eq;
+
+    fn format(lookup: &str, value: &str) -> (String, String) {
+        let (key, spec) = Spec::split(lookup.as_bytes());
+        let value = spec.apply(Cow::Borrowed(value.as_bytes()));
+        (
+            String::from_utf8_lossy(key).into_owned(),
+            String::from_utf8_lossy(&value).into_owned(),
+        )
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(format("name", "abc"), ("name".into(), "abc".into()));
+        assert_eq!(format("name:>8", "abc"), ("name".into(), "     abc".into()));
+        assert_eq!(format("1:0>3", "7"), ("1".into(), "007".into()));
+        assert_eq!(format("1:03", "7"), ("1".into(), "007".into()));
+        assert_eq!(format("ext:upper", "tar"), ("ext".into(), "TAR".into()));
+        assert_eq!(format(":<4", "ab"), ("".into(), "ab  ".into()));
+        assert_eq!(format("1::>3", "a"), ("1".into(), "::a".into()));
+        assert_eq!(format("env:HOME", "x"), ("env:HOME".into(), "x".into()));
+        assert_eq!(
+            format("env:HOME:lower", "X"),
+            ("env:HOME".into(), "x".into())
+        );
+        assert_eq!(format("now:%H:%M", "x"), ("now:%H:%M".into(), "x".into()));
+    }
+
+    #[test]
+    fn split_explicit() {
+        assert_eq!(format("now:%H:00|", "x"), ("now:%H:00".into(), "x".into()));
+        assert_eq!(format("now:%M:30|", "x"), ("now:%M:30".into(), "x".into()));
+        assert_eq!(format("now:+%s|", "x"), ("now:+%s".into(), "x".into()));
+        assert_eq!(
+            format("now:%H:00|>7", "12:00"),
+            ("now:%H:00".into(), "  12:00".into())
+        );
+        assert_eq!(format("k|upper:>3", "a"), ("k".into(), "  A".into()));
+        assert_eq!(format("k:-a|b", "x"), ("k".into(), "x".into()));
+        assert_eq!(resolve("k:-a|b", None), Some("a|b".into()));
+        assert_eq!(resolve("env:UNSET|-unset", None), Some("unset".into()));
+    }
+
+    #[test]
+    fn split_provider_names() {
+        assert_eq!(format("now:upper", "x"), ("now".into(), "X".into()));
+        assert_eq!(format("env:upper", "x"), ("env".into(), "X".into()));
+        assert_eq!(format("mtime:>3", "x"), ("mtime".into(), "  x".into()));
+        assert_eq!(resolve("now:-dflt", None), Some("dflt".into()));
+        assert_eq!(resolve("env:-dflt", None), Some("dflt".into()));
+        assert_eq!(resolve("env:+set", Some("v")), Some("set".into()));
+    }
+
+    #[test]
+    fn transforms() {
+        assert_eq!(format("k:^7", "abc"), ("k".into(), "  abc  ".into()));
+        assert_eq!(format("k:*^6", "abc"), ("k".into(), "*abc**".into()));
+        assert_eq!(format("k:.2", "café"), ("k".into(), "ca".into()));
+        assert_eq!(format("k:.4", "café1"), ("k".into(), "café".into()));
+        assert_eq!(format("k:é>5.3", "abcdef"), ("k".into(), "ééabc".into()));
+        assert_eq!(format("k:lower:.3:>4", "ABCD"), ("k".into(), " abc".into()));
+        assert_eq!(format("k:upper", "straße"), ("k".into(), "STRASSE".into()));
+        assert_eq!(format("k:2", "abc"), ("k".into(), "abc".into()));
+    }
+
+    #[test]
+    fn width_limit() {
+        assert_eq!(format("k:>65535", "").1.len(), MAX_WIDTH);
+        assert_eq!(Spec::parse(b">65536"), None);
+        assert_eq!(Spec::parse(b"99999999999999999"), None);
+        assert_eq!(Spec::parse(b".99999999999999999999999"), None);
+        assert_eq!(
+            format("0:99999999999999999", "a"),
+            ("0:99999999999999999".into(), "a".into())
+        );
+    }
+
+    fn resolve(lookup: &str, value: Option<&str>) -> Option<String> {
+        let (_, spec) = Spec::split(lookup.as_bytes());
+        spec.resolve(value.map(|value| Cow::Borrowed(value.as_bytes())).ok_or(()))
+            .ok()
+            .map(|value| String::from_utf8_lossy(&value).into_owned())
+    }
+
+    #[test]
+    fn cond() {
+        assert_eq!(resolve("k:-none", None), Some("none".into()));
+        assert_eq!(resolve("k:-none", Some("")), Some("none".into()));
+        assert_eq!(resolve("k:-none", Some("v")), Some("v".into()));
+        assert_eq!(resolve("k:-", None), Some("".into()));
+        assert_eq!(resolve("k:-a:b", None), Some("a:b".into()));
+        assert_eq!(resolve("k:>3:-7", None), Some("  7".into()));
+        assert_eq!(resolve("k:+yes", Some("v")), Some("yes".into()));
+        assert_eq!(resolve("k:+yes", Some("")), Some("".into()));
+        assert_eq!(resolve("k:+yes", None), Some("".into()));
+        assert_eq!(resolve("k:upper", None), None);
+        assert_eq!(resolve("env:HOME:-/root", None), Some("/root".into()));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let (_, spec) = Spec::split(b"k:>4:upper");
+        assert_eq!(
+            spec.apply(Cow::Borrowed(b"a\xFFb")).as_ref(),
+            b" A\xFFB".as_slice()
+        );
+
+        let (_, spec) = Spec::split(b"k:.2");
+        assert_eq!(
+            spec.apply(Cow::Borrowed(b"\xFF\xFEa")).as_ref(),
+            b"\xFF\xFE".as_slice()
+        );
+    }
+}
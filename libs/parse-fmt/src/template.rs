@@ -0,0 +1,211 @@
+//! Precompiled format strings.
+//!
+//! A [Template] parses a format string once, such that the keys it uses may be inspected and
+//! validated up front, and it may then be rendered any amount of times.
+
+use ::std::{borrow::Cow, convert::Infallible};
+
+use crate::{FormatError, parse_fmt, spec::Spec};
+
+/// A part of a template.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Part<K> {
+    /// Text which is used as is.
+    Text(Vec<u8>),
+    /// A lookup of key with the spec to apply to the result.
+    Lookup {
+        /// Key to look up.
+        key: K,
+        /// Spec applied to the result of lookup.
+        spec: Spec<'static>,
+    },
+}
+
+/// A parsed format string owning it's parts.
+///
+/// Keys are of type `K`, by default the bytes of the key with empty keys numbered in order of
+/// appearance, such that `{}, {}, {5}` has the keys `0`, `1` and `5`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Template<K = Vec<u8>> {
+    /// Parts of template, with adjacent text merged.
+    parts: Vec<Part<K>>,
+}
+
+impl Template {
+    /// Parse a format string into a template.
+    ///
+    /// # Errors
+    /// If the format string cannot be parsed.
+    pub fn parse(fmt: &[u8]) -> Result<Self, FormatError<'_, Infallible>> {
+        let mut idx_counter = 0usize;
+        Self::parse_with(fmt, |key| {
+            Ok(if key.is_empty() {
+                let key = idx_counter.to_string().into_bytes();
+                idx_counter += 1;
+                key
+            } else {
+                key.to_vec()
+            })
+        })
+    }
+}
+
+impl<K> Template<K> {
+    /// Parse a format string into a template, using key_parser to parse the keys of lookups.
+    ///
+    /// The key parser is called once per lookup in order of appearance.
+    ///
+    /// # Errors
+    /// If the format string cannot be parsed, or if key_parser fails, in which case the error is
+    /// returned as [FormatError::Lookup].
+    pub fn parse_with<'a, E>(
+        fmt: &'a [u8],
+        mut key_parser: impl FnMut(&'a [u8]) -> Result<K, E>,
+    ) -> Result<Self, FormatError<'a, E>> {
+        let mut parts = Vec::new();
+        for chunk in parse_fmt(fmt) {
+            match chunk.map_err(FormatError::Parse)? {
+                crate::Chunk::Text(text) => match parts.last_mut() {
+                    Some(Part::Text(prev)) => prev.extend_from_slice(text),
+                    _ => parts.push(Part::Text(text.to_vec())),
+                },
+                crate::Chunk::Lookup(items) => {
+                    let (key, spec) = Spec::split(items);
+                    let key = key_parser(key).map_err(FormatError::Lookup)?;
+                    parts.push(Part::Lookup {
+                        key,
+                        spec: spec.into_owned(),
+                    });
+                }
+            }
+        }
+        Ok(Self { parts })
+    }
+
+    /// Get the keys of all lookups in order of appearance.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Text(_) => None,
+            Part::Lookup { key, .. } => Some(key),
+        })
+    }
+
+    /// Check that all keys are declared.
+    ///
+    /// # Errors
+    /// With the first key for which is_declared returns false.
+    pub fn validate(&self, mut is_declared: impl FnMut(&K) -> bool) -> Result<(), &K> {
+        match self.keys().find(|key| !is_declared(key)) {
+            Some(key) => Err(key),
+            None => Ok(()),
+        }
+    }
+
+    /// Render template to the end of a buffer, using lookup to resolve keys.
+    ///
+    /// # Errors
+    /// If a lookup fails, and the spec of the lookup has no condition.
+    pub fn render_to<'s, 'v, C, E, F>(&'s self, buf: &mut C, mut lookup: F) -> Result<(), E>
+    where
+        C: Extend<u8>,
+        F: FnMut(&'s K) -> Result<Cow<'v, [u8]>, E>,
+    {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => buf.extend(text.iter().copied()),
+                Part::Lookup { key, spec } => {
+                    let value = spec.resolve(lookup(key))?;
+                    buf.extend(value.iter().copied());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render template to a new container, using lookup to resolve keys.
+    ///
+    /// # Errors
+    /// If a lookup fails, and the spec of the lookup has no condition.
+    pub fn render<'s, 'v, C, E, F>(&'s self, lookup: F) -> Result<C, E>
+    where
+        C: Extend<u8> + Default,
+        F: FnMut(&'s K) -> Result<Cow<'v, [u8]>, E>,
+    {
+        let mut c = C::default();
+        self.render_to(&mut c, lookup)?;
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::lookup::{self, SeqLookupError};
+
+    #[test]
+    fn keys() {
+        let template = Template::parse(b"{{{}}}, {name:>4}, {}, {5:-x}");
+        assert_eq!(
+            template
+                .as_ref()
+                .map(|template| template.keys().map(Vec::as_slice).collect::<Vec<_>>()),
+            Ok(Vec::from([&b"0"[..], b"name", b"1", b"5"])),
+        );
+
+        let declared: [&[u8]; 3] = [b"0", b"1", b"name"];
+        assert_eq!(
+            template
+                .as_ref()
+                .map(|template| template.validate(|key| declared.contains(&&key[..]))),
+            Ok(Err(&b"5".to_vec()))
+        );
+        assert_eq!(
+            template
+                .as_ref()
+                .map(|template| template.validate(|key| key != b"6")),
+            Ok(Ok(()))
+        );
+    }
+
+    #[test]
+    fn render() {
+        let values = [&b"zero"[..], b"one"];
+        let template = Template::parse(b"{{{}}}, {1:upper}, {-1:.1}, {5:-none}");
+        let missing = Template::parse(b"{2}");
+
+        let mut lookup = lookup::seq(&values);
+        let mut buf = Vec::new();
+        for _ in 0..2 {
+            buf.clear();
+            assert_eq!(
+                template
+                    .as_ref()
+                    .map(|template| template.render_to(&mut buf, |key| lookup(key))),
+                Ok(Ok(()))
+            );
+            assert_eq!(buf, b"{zero}, ONE, o, none");
+        }
+
+        assert_eq!(
+            missing
+                .as_ref()
+                .map(|missing| missing.render::<Vec<u8>, _, _>(|key| lookup(key))),
+            Ok(Err(SeqLookupError::OutOfRange(2, -2..2)))
+        );
+    }
+
+    #[test]
+    fn parse_with() {
+        let template = Template::parse_with(b"{a}{b}", |key| match key {
+            b"a" => Ok('a'),
+            key => Err(key),
+        });
+        assert_eq!(template, Err(FormatError::Lookup(&b"b"[..])));
+        assert_eq!(
+            Template::parse(b"{a").map_err(|err| matches!(err, FormatError::Parse(_))),
+            Err(true)
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! [Boundary] implementation.
+
+use ::clap::ValueEnum;
+use ::unicode_segmentation::UnicodeSegmentation;
+
+/// Where byte prefixes and suffixes may end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Boundary {
+    /// At any byte.
+    #[default]
+    Byte,
+    /// At the boundary of a code point.
+    Char,
+    /// At the boundary of an extended grapheme cluster.
+    Grapheme,
+}
+
+impl Boundary {
+    /// Split bytes into the units between boundaries.
+    ///
+    /// Bytes which are not valid utf-8 are each a unit of their own.
+    pub fn units(self, bytes: &[u8]) -> Vec<&[u8]> {
+        if self == Self::Byte {
+            return bytes.chunks(1).collect();
+        }
+
+        let mut units = Vec::new();
+        let mut at = 0;
+        for chunk in bytes.utf8_chunks() {
+            let valid = chunk.valid();
+            let mut push = |len: usize| {
+                units.push(&bytes[at..at + len]);
+                at += len;
+            };
+            if self == Self::Grapheme {
+                valid.graphemes(true).for_each(|unit| push(unit.len()));
+            } else {
+                valid.chars().for_each(|unit| push(unit.len_utf8()));
+            }
+
+            for _ in chunk.invalid() {
+                units.push(&bytes[at..at + 1]);
+                at += 1;
+            }
+        }
+        units
+    }
+}
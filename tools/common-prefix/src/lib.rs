@@ -1,20 +1,31 @@
 #![doc = include_str!("../README.md")]
 
 use ::std::{
+    borrow::Cow,
     cmp::Ordering,
-    io::{Read, Write},
+    io::{BufRead, Read, Write},
 };
 
+use ::file_suite_common::ExitCodeError;
+use ::insensitive_buf::Insensitive;
 use ::itertools::{Itertools, MinMaxResult};
 use ::regex::bytes::{Regex, RegexBuilder};
 
+use crate::{boundary::Boundary, tree::Node};
+
+mod boundary;
+mod tree;
+
 fn compile_re(pattern: &str) -> Result<Regex, ::regex::Error> {
     RegexBuilder::new(pattern).multi_line(true).build()
 }
 
-/// Find the common prefix of all lines piped to stdin.
+/// Exit status used when the common prefix, or suffix, is empty.
+const EMPTY_EXIT_CODE: u8 = 2;
+
+/// Find the common prefix, or suffix, of all lines piped to stdin.
 ///
-/// Empty lines are ignored.
+/// Empty lines are ignored. Exits with status 2 if the prefix is empty.
 #[derive(Debug, ::clap::Parser)]
 pub struct Cli {
     /// Input is separated by null characters.
@@ -34,10 +45,103 @@ pub struct Cli {
     )]
     components: Option<Regex>,
 
+    /// Find the common suffix instead of the common prefix.
+    #[arg(long, short)]
+    suffix: bool,
+
+    /// Where byte prefixes and suffixes may end, such that they do not end within a code point
+    /// or grapheme cluster.
+    #[arg(
+        long,
+        short,
+        value_enum,
+        default_value_t,
+        conflicts_with = "components"
+    )]
+    boundary: Boundary,
+
+    /// Instead of a single prefix, print a tree of the longest prefixes shared by at least
+    /// 'min-count' inputs, each with the amount of inputs sharing it.
+    ///
+    /// Prefixes are printed as indented text with the count first, or as json if 'json' is
+    /// given.
+    #[arg(
+        long,
+        short,
+        conflicts_with_all = ["suffix", "print_pairs", "strip", "relative_to_prefix"]
+    )]
+    tree: bool,
+
+    /// Minimum amount of inputs sharing a prefix for it to be part of the tree.
+    #[arg(long, short, default_value_t = 2, requires = "tree", value_name = "N")]
+    min_count: usize,
+
+    /// Print the tree as json.
+    #[arg(long, requires = "tree")]
+    json: bool,
+
+    /// Compare inputs ignoring case, the output keeps the casing of the first input.
+    ///
+    /// Without 'components' prefixes and suffixes end at code point boundaries at the least.
+    #[arg(long, short)]
+    ignore_case: bool,
+
     /// After finding the prefix, print all non-empty input lines and them without the prefix
     /// separated by newlines or null bytes depending on the 'null' option.
     #[arg(long, visible_alias = "pairs")]
     print_pairs: bool,
+
+    /// Only print all non-empty input lines without the prefix, each terminated by a newline
+    /// or null byte depending on the 'null' option.
+    #[arg(long, conflicts_with = "print_pairs")]
+    strip: bool,
+
+    /// Print inputs without the prefix as paths relative to it, starting with './', or as '.'
+    /// if nothing remains.
+    ///
+    /// Used in place of 'strip', or to change the stripped lines of 'print-pairs'.
+    #[arg(long, requires = "components", conflicts_with = "suffix")]
+    relative_to_prefix: bool,
+}
+
+/// Find the common suffix of the items ordered first and last by their reversed bytes.
+///
+/// # Errors
+/// If there are no items.
+fn by_byte_suffix(items: MinMaxResult<&[u8]>) -> ::std::io::Result<&[u8]> {
+    match items {
+        MinMaxResult::NoElements => Err(::std::io::Error::other("input is empty")),
+        MinMaxResult::OneElement(line) => Ok(line),
+        MinMaxResult::MinMax(first, last) => {
+            let len = first
+                .iter()
+                .rev()
+                .zip(last.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            Ok(&first[first.len() - len..])
+        }
+    }
+}
+
+/// Compare bytes starting from the end.
+fn cmp_rev(a: &[u8], b: &[u8]) -> Ordering {
+    ::std::iter::Iterator::cmp(a.iter().rev(), b.iter().rev())
+}
+
+/// Find the common prefix, or suffix, of items by bytes.
+///
+/// # Errors
+/// If there are no items.
+fn by_byte<'a>(
+    items: impl IntoIterator<Item = &'a [u8]>,
+    suffix: bool,
+) -> ::std::io::Result<&'a [u8]> {
+    if suffix {
+        by_byte_suffix(items.into_iter().minmax_by(|a, b| cmp_rev(a, b)))
+    } else {
+        by_byte_prefix(items.into_iter().minmax())
+    }
 }
 
 fn by_byte_prefix(items: MinMaxResult<&[u8]>) -> ::std::io::Result<&[u8]> {
@@ -64,151 +168,410 @@ fn by_byte_prefix(items: MinMaxResult<&[u8]>) -> ::std::io::Result<&[u8]> {
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/// A component of an item, either a value followed by a separator, or a unit without one.
+#[derive(Debug, Clone, Copy)]
 struct Component<'b> {
     pub value: &'b [u8],
     pub sep: Option<&'b [u8]>,
+    /// Compare values ignoring case.
+    pub ignore_case: bool,
 }
 
-impl<'b> AsRef<Component<'b>> for Component<'b> {
-    fn as_ref(&self) -> &Component<'b> {
-        self
+impl PartialEq for Component<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Component<'_> {}
+
+impl PartialOrd for Component<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Component<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.ignore_case || other.ignore_case {
+            Insensitive::from_bytes(self.value).cmp(Insensitive::from_bytes(other.value))
+        } else {
+            self.value.cmp(other.value)
+        }
+        .then_with(|| self.sep.cmp(&other.sep))
     }
 }
 
 impl<'b> Component<'b> {
-    fn parse(re: &Regex, bytes: &'b [u8], at: usize) -> (Self, usize) {
+    fn parse(re: &Regex, bytes: &'b [u8], at: usize, ignore_case: bool) -> (Self, usize) {
         if let Some(mat) = re.find_at(bytes, at) {
             let value = &bytes[at..mat.start()];
             let sep = Some(mat.as_bytes());
-            (Self { value, sep }, mat.end())
+            (
+                Self {
+                    value,
+                    sep,
+                    ignore_case,
+                },
+                mat.end(),
+            )
         } else {
             let value = &bytes[at..];
             let sep = None;
-            (Self { value, sep }, bytes.len())
+            (
+                Self {
+                    value,
+                    sep,
+                    ignore_case,
+                },
+                bytes.len(),
+            )
         }
     }
 
-    fn parse_all(re: &'b Regex, bytes: &'b [u8]) -> impl 'b + Iterator<Item = Self> {
+    fn parse_all(
+        re: &'b Regex,
+        bytes: &'b [u8],
+        ignore_case: bool,
+    ) -> impl 'b + Iterator<Item = Self> {
         let mut at = 0;
         ::std::iter::from_fn(move || {
             if at >= bytes.len() {
                 return None;
             }
             let comp;
-            (comp, at) = Self::parse(re, bytes, at);
+            (comp, at) = Self::parse(re, bytes, at, ignore_case);
             Some(comp)
         })
     }
 
-    fn write_all(
-        comps: impl IntoIterator<Item = impl AsRef<Self>>,
-        mut w: impl Write,
-    ) -> ::std::io::Result<()> {
+    fn write_all(comps: &[Self], mut w: impl Write) -> ::std::io::Result<()> {
         for comp in comps {
-            w.write_all(comp.as_ref().value)?;
-            if let Some(sep) = comp.as_ref().sep {
+            w.write_all(comp.value)?;
+            if let Some(sep) = comp.sep {
                 w.write_all(sep)?;
             }
         }
         Ok(())
     }
 
-    fn cmp_multiple(a: &[Self], b: &[Self]) -> Ordering {
-        let a = a.iter().map(|comp| comp.value);
-        let b = b.iter().map(|comp| comp.value);
-        ::std::iter::Iterator::cmp(a, b)
+    /// Length of component value and separator.
+    fn len(&self) -> usize {
+        self.value.len() + self.sep.map_or(0, <[u8]>::len)
+    }
+
+    /// Concatenate components and their separators.
+    fn to_bytes(comps: &[Self]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for comp in comps {
+            bytes.extend_from_slice(comp.value);
+            if let Some(sep) = comp.sep {
+                bytes.extend_from_slice(sep);
+            }
+        }
+        bytes
     }
 }
 
-fn by_component<'a>(
-    items: impl IntoIterator<Item = impl AsRef<[Component<'a>]>>,
-    w: impl Write,
-) -> ::std::io::Result<usize> {
-    let items = items
-        .into_iter()
-        .minmax_by(|a, b| Component::cmp_multiple(a.as_ref(), b.as_ref()));
+/// Find the common prefix, or suffix, of items by component, returning the first item and the
+/// amount of components in the prefix.
+///
+/// The prefix is returned as part of the first item such that it keeps the casing of it when
+/// components are compared ignoring case.
+///
+/// # Errors
+/// If there are no items.
+fn by_component<'a, T: AsRef<[Component<'a>]> + Clone>(
+    items: impl IntoIterator<Item = T>,
+    suffix: bool,
+) -> ::std::io::Result<(T, usize)> {
+    let mut items = items.into_iter().peekable();
+    let first = items
+        .peek()
+        .cloned()
+        .ok_or_else(|| ::std::io::Error::other("input is empty"))?;
+
+    let len = if suffix {
+        match items.minmax_by(|a, b| {
+            ::std::iter::Iterator::cmp(a.as_ref().iter().rev(), b.as_ref().iter().rev())
+        }) {
+            MinMaxResult::MinMax(min, max) => min
+                .as_ref()
+                .iter()
+                .rev()
+                .zip(max.as_ref().iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count(),
+            _ => first.as_ref().len(),
+        }
+    } else {
+        match items.minmax_by(|a, b| a.as_ref().cmp(b.as_ref())) {
+            MinMaxResult::MinMax(min, max) => min
+                .as_ref()
+                .iter()
+                .zip(max.as_ref())
+                .take_while(|(a, b)| a == b)
+                .count(),
+            _ => first.as_ref().len(),
+        }
+    };
 
-    match items {
-        MinMaxResult::NoElements => Err(::std::io::Error::other("input is empty")),
-        MinMaxResult::OneElement(elem) => {
-            Component::write_all(elem.as_ref(), w)?;
-            Ok(elem.as_ref().len())
+    Ok((first, len))
+}
+
+impl Cli {
+    /// Boundary to split items at when not using components, code points at the least if case
+    /// is ignored.
+    fn boundary(&self) -> Boundary {
+        if self.ignore_case && self.boundary == Boundary::Byte {
+            Boundary::Char
+        } else {
+            self.boundary
         }
-        MinMaxResult::MinMax(first, last) => {
-            let prefix = {
-                let mut prefix: &[Component] = &[];
-                for i in 0.. {
-                    let Some(initial) = first.as_ref().get(0..i) else {
-                        break;
-                    };
-                    if !last.as_ref().starts_with(initial) {
-                        break;
-                    }
-                    prefix = initial;
-                }
+    }
+
+    /// If items should be split into components, or units by boundary, instead of being
+    /// compared by bytes.
+    fn use_components(&self) -> bool {
+        self.components.is_some() || self.boundary() != Boundary::Byte
+    }
+
+    /// Split an item into components, or into units by boundary if no component pattern is
+    /// given.
+    fn components<'a>(&'a self, bytes: &'a [u8]) -> Vec<Component<'a>> {
+        let ignore_case = self.ignore_case;
+        if let Some(pat) = &self.components {
+            Component::parse_all(pat, bytes, ignore_case).collect()
+        } else {
+            self.boundary()
+                .units(bytes)
+                .into_iter()
+                .map(|value| Component {
+                    value,
+                    sep: None,
+                    ignore_case,
+                })
+                .collect()
+        }
+    }
+
+    /// Get the length in bytes of the common prefix, or suffix, of prefix and item, as it is
+    /// in prefix.
+    fn common_len(&self, prefix: &[u8], item: &[u8]) -> usize {
+        if self.use_components() {
+            let (prefix, item) = (self.components(prefix), self.components(item));
+            if self.suffix {
                 prefix
+                    .iter()
+                    .rev()
+                    .zip(item.iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(comp, _)| comp.len())
+                    .sum()
+            } else {
+                prefix
+                    .iter()
+                    .zip(&item)
+                    .take_while(|(a, b)| a == b)
+                    .map(|(comp, _)| comp.len())
+                    .sum()
+            }
+        } else if self.suffix {
+            prefix
+                .iter()
+                .rev()
+                .zip(item.iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count()
+        } else {
+            prefix.iter().zip(item).take_while(|(a, b)| a == b).count()
+        }
+    }
+
+    /// Find the common prefix, or suffix, of items as they are read, only keeping the current
+    /// prefix in memory and stopping early once it is empty.
+    ///
+    /// # Errors
+    /// If there are no items or reading fails.
+    fn stream_prefix(&self, mut r: impl BufRead, delim: u8) -> ::std::io::Result<Vec<u8>> {
+        let mut prefix = None::<Vec<u8>>;
+        let mut item = Vec::new();
+        loop {
+            item.clear();
+            if r.read_until(delim, &mut item)? == 0 {
+                break;
+            }
+            if item.last() == Some(&delim) {
+                item.pop();
+            }
+            if item.is_empty() {
+                continue;
+            }
+
+            let Some(prefix) = &mut prefix else {
+                prefix = Some(item.clone());
+                continue;
             };
-            Component::write_all(prefix, w)?;
-            Ok(prefix.as_ref().len())
+
+            let len = self.common_len(prefix, &item);
+            if self.suffix {
+                prefix.drain(..prefix.len() - len);
+            } else {
+                prefix.truncate(len);
+            }
+
+            if prefix.is_empty() {
+                break;
+            }
+        }
+        prefix.ok_or_else(|| ::std::io::Error::other("input is empty"))
+    }
+
+    /// Print a prefix tree of items.
+    ///
+    /// # Errors
+    /// If there are no items or writing fails.
+    fn print_tree<T: Ord>(
+        &self,
+        items: impl IntoIterator<Item = impl AsRef<[T]>>,
+        delim: u8,
+        prefix_bytes: impl Fn(&[T]) -> Cow<[u8]>,
+    ) -> ::std::io::Result<()> {
+        let items = items.into_iter().collect::<Vec<_>>();
+        let mut items = items
+            .iter()
+            .map(AsRef::as_ref)
+            .enumerate()
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(_, item)| *item);
+
+        if items.is_empty() {
+            return Err(::std::io::Error::other("input is empty"));
+        }
+        let Some(root) =
+            Node::build(&items, 0, self.min_count).filter(|_| items.len() >= self.min_count)
+        else {
+            return Ok(());
+        };
+
+        let mut stdout = ::std::io::stdout().lock();
+        if self.json {
+            ::serde_json::to_writer(&mut stdout, &root.to_json(&prefix_bytes))?;
+            stdout.write_all(b"\n")
+        } else {
+            root.write_text(&mut stdout, delim, 0, &prefix_bytes)
         }
     }
 }
 
+/// Get an exit code error if the prefix of length len is empty.
+///
+/// # Errors
+/// If len is 0.
+fn empty_status(len: usize) -> Result<(), ExitCodeError> {
+    if len == 0 {
+        Err(EMPTY_EXIT_CODE.into())
+    } else {
+        Ok(())
+    }
+}
+
 impl ::file_suite_common::Run for Cli {
-    type Error = ::std::io::Error;
+    type Error = ::color_eyre::Report;
 
     fn run(self) -> Result<(), Self::Error> {
+        let delim = if self.null { b'\0' } else { b'\n' };
+
+        if !self.tree && !self.print_pairs && !self.strip && !self.relative_to_prefix {
+            let prefix = self.stream_prefix(::std::io::stdin().lock(), delim)?;
+            ::std::io::stdout().lock().write_all(&prefix)?;
+            return Ok(empty_status(prefix.len())?);
+        }
+
         let bytes = {
             let mut buf = Vec::new();
             ::std::io::stdin().lock().read_to_end(&mut buf)?;
             buf
         };
 
-        let delim = if self.null { b'\0' } else { b'\n' };
         let items = bytes.split(|e| *e == delim).filter(|line| !line.is_empty());
 
-        if let Some(pat) = self.components {
-            let items = items.map(|bytes| Component::parse_all(&pat, bytes).collect::<Vec<_>>());
+        if self.tree {
+            return Ok(if self.use_components() {
+                self.print_tree(items.map(|bytes| self.components(bytes)), delim, |prefix| {
+                    Cow::Owned(Component::to_bytes(prefix))
+                })
+            } else {
+                self.print_tree(items, delim, |prefix| Cow::Borrowed(prefix))
+            }?);
+        }
+
+        // Prefix or suffix of length len of item.
+        let affix = |len: usize, item_len: usize| {
+            if self.suffix {
+                item_len - len..item_len
+            } else {
+                0..len
+            }
+        };
 
+        // Item with the prefix or suffix of length len removed.
+        let strip = |len: usize, item_len: usize| {
+            if self.suffix {
+                0..item_len - len
+            } else {
+                len..item_len
+            }
+        };
+
+        let mut stdout = ::std::io::stdout().lock();
+        let len = if self.use_components() {
+            let items = items
+                .map(|bytes| self.components(bytes))
+                .collect::<Vec<_>>();
+            let (first, len) = by_component(&items, self.suffix)?;
             if self.print_pairs {
-                let items = items.collect::<Vec<_>>();
-                let mut stdout = ::std::io::stdout().lock();
-                let len = by_component(&items, &mut stdout)?;
+                Component::write_all(&first[affix(len, first.len())], &mut stdout)?;
+            }
 
-                for item in items {
+            for item in &items {
+                if self.print_pairs {
                     stdout.write_all(&[delim])?;
-                    Component::write_all(&item, &mut stdout)?;
+                    Component::write_all(item, &mut stdout)?;
+                    stdout.write_all(&[delim])?;
+                }
+                let stripped = &item[strip(len, item.len())];
+                if self.relative_to_prefix {
+                    stdout.write_all(if stripped.is_empty() { b"." } else { b"./" })?;
+                }
+                Component::write_all(stripped, &mut stdout)?;
+                if !self.print_pairs {
                     stdout.write_all(&[delim])?;
-                    Component::write_all(&item[len..], &mut stdout)?;
                 }
-
-                Ok(())
-            } else {
-                by_component(items, ::std::io::stdout().lock())?;
-                Ok(())
             }
+            len
         } else {
+            let items = items.collect::<Vec<_>>();
+            let prefix = by_byte(items.iter().copied(), self.suffix)?;
+            let len = prefix.len();
             if self.print_pairs {
-                let items = items.collect::<Vec<_>>();
-                let prefix = by_byte_prefix(items.iter().copied().minmax())?;
-                let len = prefix.len();
-
-                let mut stdout = ::std::io::stdout().lock();
                 stdout.write_all(prefix)?;
+            }
 
-                for item in items {
+            for item in items {
+                if self.print_pairs {
                     stdout.write_all(&[delim])?;
                     stdout.write_all(item)?;
                     stdout.write_all(&[delim])?;
-                    stdout.write_all(&item[len..])?;
+                    stdout.write_all(&item[strip(len, item.len())])?;
+                } else {
+                    stdout.write_all(&item[strip(len, item.len())])?;
+                    stdout.write_all(&[delim])?;
                 }
-
-                Ok(())
-            } else {
-                let prefix = by_byte_prefix(items.minmax())?;
-                ::std::io::stdout().lock().write_all(prefix)
             }
-        }
+            len
+        };
+
+        Ok(empty_status(len)?)
     }
 }
@@ -0,0 +1,84 @@
+//! Prefix tree clustering of inputs.
+
+use ::std::{borrow::Cow, io::Write};
+
+use ::serde_json::{Value, json};
+
+/// A node of a prefix tree, the longest prefix shared by count items.
+#[derive(Debug)]
+pub struct Node<'a, T> {
+    /// Prefix shared by all items of node.
+    prefix: &'a [T],
+    /// Amount of items with prefix.
+    count: usize,
+    /// Nodes for longer prefixes shared by enough items.
+    children: Vec<Node<'a, T>>,
+}
+
+impl<'a, T: Eq> Node<'a, T> {
+    /// Build a tree from sorted items which all share a prefix of length depth, only keeping
+    /// nodes with at least min_count items.
+    ///
+    /// Items are paired with their index in the input, the prefix of a node is taken from the
+    /// item which came first in the input.
+    ///
+    /// Returns `None` if there are no items.
+    pub fn build(items: &[(usize, &'a [T])], depth: usize, min_count: usize) -> Option<Self> {
+        let ((_, first), (_, last)) = (items.first()?, items.last()?);
+        let len = depth
+            + first[depth..]
+                .iter()
+                .zip(&last[depth..])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+        // Items ending at the prefix sort first, and belong to no child.
+        let rest = &items[items.partition_point(|(_, item)| item.len() == len)..];
+        let children = rest
+            .chunk_by(|(_, a), (_, b)| a[len] == b[len])
+            .filter(|group| group.len() >= min_count)
+            .filter_map(|group| Self::build(group, len + 1, min_count))
+            .collect();
+
+        let (_, earliest) = items.iter().min_by_key(|(idx, _)| idx)?;
+        Some(Self {
+            prefix: &earliest[..len],
+            count: items.len(),
+            children,
+        })
+    }
+
+    /// Write tree as indented text, one node per line as the count and the prefix separated by a
+    /// tab, with the prefix indented by two spaces per level.
+    ///
+    /// # Errors
+    /// If writing fails.
+    pub fn write_text(
+        &self,
+        w: &mut impl Write,
+        delim: u8,
+        level: usize,
+        prefix_bytes: &impl Fn(&[T]) -> Cow<[u8]>,
+    ) -> ::std::io::Result<()> {
+        write!(w, "{}\t{:indent$}", self.count, "", indent = level * 2)?;
+        w.write_all(&prefix_bytes(self.prefix))?;
+        w.write_all(&[delim])?;
+        for child in &self.children {
+            child.write_text(w, delim, level + 1, prefix_bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Convert tree to json, with prefixes converted lossily to utf-8.
+    pub fn to_json(&self, prefix_bytes: &impl Fn(&[T]) -> Cow<[u8]>) -> Value {
+        json!({
+            "prefix": String::from_utf8_lossy(&prefix_bytes(self.prefix)),
+            "count": self.count,
+            "children": self
+                .children
+                .iter()
+                .map(|child| child.to_json(prefix_bytes))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
@@ -0,0 +1,153 @@
+//! [OnCollision] implementation.
+
+use ::std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt::Write,
+    path::Path,
+};
+
+use ::clap::ValueEnum;
+use ::color_eyre::{Section, eyre::eyre};
+use ::sha2::{Digest, Sha256};
+
+use crate::entry::Entry;
+
+/// What to do when multiple sources are given the same name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnCollision {
+    /// Fail before anything is linked.
+    Error,
+    /// Only link the first source.
+    #[default]
+    Skip,
+    /// Add a counter to the names of all but the first source.
+    Suffix,
+    /// Add a hash of the source path to the names of all but the first source.
+    Hash,
+    /// Only link the last source, replacing existing files in the output directory.
+    Overwrite,
+}
+
+/// Get name with tag added before the extension, separated by sep.
+fn tagged(name: &OsStr, sep: &OsStr, tag: &str) -> OsString {
+    let path = Path::new(name);
+    let mut tagged = OsString::from(path.file_stem().unwrap_or(name));
+    tagged.push(sep);
+    tagged.push(tag);
+    if let Some(ext) = path.extension() {
+        tagged.push(".");
+        tagged.push(ext);
+    }
+    tagged
+}
+
+/// Get a short hex hash of path.
+fn path_hash(path: &Path) -> String {
+    Sha256::digest(path.as_os_str().as_encoded_bytes())
+        .iter()
+        .take(4)
+        .fold(String::new(), |mut hash, byte| {
+            write!(hash, "{byte:02x}").expect("write to string should succeed");
+            hash
+        })
+}
+
+impl OnCollision {
+    /// Resolve name collisions between entries, removing or renaming entries according to the
+    /// policy, and logging a report of each collision.
+    ///
+    /// Entries are expected to be in a stable order, which decides what source is first.
+    ///
+    /// # Errors
+    /// If the policy is [OnCollision::Error] and any names collide.
+    ///
+    /// # Panics
+    /// Should not panic, as formatting to a string does not fail, and there is always an unused
+    /// tagged name.
+    pub fn resolve(self, mut entries: Vec<Entry>, sep: &OsStr) -> ::color_eyre::Result<Vec<Entry>> {
+        let mut groups = HashMap::<&OsStr, Vec<usize>>::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            groups.entry(&entry.name).or_default().push(idx);
+        }
+        let mut collisions = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect::<Vec<_>>();
+        if collisions.is_empty() {
+            return Ok(entries);
+        }
+        collisions.sort_unstable();
+
+        let mut report = String::new();
+        for group in &collisions {
+            let mut line = format!("'{}' is shared by", entries[group[0]].name.display());
+            for &idx in group {
+                write!(line, " '{}'", entries[idx].src.display())
+                    .expect("write to string should succeed");
+            }
+            ::log::warn!("{line}");
+            report.push_str(&line);
+            report.push('\n');
+        }
+
+        if self == Self::Error {
+            return Err(eyre!("found {} colliding output names", collisions.len()))
+                .section(report.trim_end().to_owned())
+                .suggestion("use --on-collision to choose how collisions are handled");
+        }
+
+        let mut used = entries
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect::<HashSet<_>>();
+        let mut removed = HashSet::new();
+        let mut renamed = Vec::new();
+        for group in collisions {
+            match self {
+                Self::Error | Self::Skip => removed.extend(group.iter().skip(1).copied()),
+                Self::Overwrite => removed.extend(group.iter().rev().skip(1).copied()),
+                Self::Suffix | Self::Hash => {
+                    for idx in group.into_iter().skip(1) {
+                        let entry = &entries[idx];
+                        let hash = (self == Self::Hash).then(|| path_hash(&entry.src));
+                        let name = (1usize..)
+                            .map(|n| {
+                                let tag = match &hash {
+                                    Some(hash) if n == 1 => hash.clone(),
+                                    Some(hash) => format!("{hash}-{n}"),
+                                    None => n.to_string(),
+                                };
+                                tagged(&entry.name, sep, &tag)
+                            })
+                            .find(|name| !used.contains(name))
+                            .expect("some tagged name should be unused");
+                        used.insert(name.clone());
+                        renamed.push((idx, name));
+                    }
+                }
+            }
+        }
+
+        for (idx, name) in renamed {
+            ::log::info!(
+                "naming '{src}' '{name}'",
+                src = entries[idx].src.display(),
+                name = name.display()
+            );
+            entries[idx].name = name;
+        }
+        Ok(entries
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, entry)| {
+                let keep = !removed.contains(idx);
+                if !keep {
+                    ::log::info!("skipping '{src}'", src = entry.src.display());
+                }
+                keep
+            })
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+}
@@ -0,0 +1,58 @@
+//! [Entry] implementation.
+
+use ::std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::name::Namer;
+
+/// A source file and the name it is given in the output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Index of the input directory the source was found in.
+    pub root_idx: usize,
+    /// Path of source relative to the input directory.
+    pub relative: PathBuf,
+    /// Absolute path of source.
+    pub src: PathBuf,
+    /// Name of file in output directory.
+    pub name: OsString,
+}
+
+impl Entry {
+    /// Create a new entry for src found in root, named by namer.
+    pub fn new(
+        root_idx: usize,
+        root: &Path,
+        src: PathBuf,
+        namer: &Namer,
+        counter: usize,
+    ) -> Option<Self> {
+        let relative = src
+            .strip_prefix(root)
+            .inspect_err(|err| {
+                ::log::warn!(
+                    "could not stip prefix '{root}' from '{src}', {err}",
+                    root = root.display(),
+                    src = src.display()
+                )
+            })
+            .ok()?
+            .to_path_buf();
+
+        let name = namer
+            .name(root_idx, &relative, counter)
+            .inspect_err(|err| {
+                ::log::warn!("could not name '{src}', {err}", src = src.display());
+            })
+            .ok()?;
+
+        Some(Self {
+            root_idx,
+            relative,
+            src,
+            name,
+        })
+    }
+}
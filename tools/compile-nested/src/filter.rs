@@ -0,0 +1,209 @@
+//! [Filter] implementation.
+
+use ::std::{
+    ffi::OsString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use ::clap::{Args, ValueEnum};
+use ::regex::bytes::Regex;
+use ::walkdir::{DirEntry, WalkDir};
+
+/// How should hidden files and directories be treated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum HiddenBehaviour {
+    /// Skip hidden files, and do not descend into hidden directories.
+    #[value(alias = "s")]
+    Skip,
+    /// Include hidden files and directories.
+    #[value(alias = "i")]
+    #[default]
+    Include,
+}
+
+/// Filter configuration.
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Filters")]
+pub struct Filter {
+    /// Only compile files with paths matching provided regex.
+    ///
+    /// Matched against the full path of files, may be given multiple times in which case
+    /// files matching any of them are compiled.
+    #[arg(long, visible_alias = "re", value_parser = Regex::new)]
+    pub regex: Vec<Regex>,
+
+    /// Skip files with paths matching provided regex.
+    ///
+    /// Matched against the full path of files, may be given multiple times.
+    #[arg(long, visible_alias = "exclude-re", value_parser = Regex::new)]
+    pub exclude_regex: Vec<Regex>,
+
+    /// Only compile files matching provided glob.
+    ///
+    /// Globs containing a '/' are matched against the path relative to the input directory,
+    /// others against the file name. '*' and '?' do not match '/', '**' does, and '[...]'
+    /// matches a set of characters. May be given multiple times in which case files matching
+    /// any of them are compiled.
+    #[arg(long, value_parser = glob)]
+    pub glob: Vec<Glob>,
+
+    /// Skip files matching provided glob, matched as for '--glob'.
+    #[arg(long, value_parser = glob)]
+    pub exclude_glob: Vec<Glob>,
+
+    /// Only compile files with one of the given extensions, compared ignoring ascii case.
+    ///
+    /// Extensions are given without the leading dot, either comma separated or by giving
+    /// the option multiple times.
+    #[arg(long, value_delimiter = ',', value_name = "EXT")]
+    pub ext: Vec<OsString>,
+
+    /// How to handle hidden files and directories, those with names starting with a dot.
+    #[arg(long, value_enum, default_value_t)]
+    pub hidden: HiddenBehaviour,
+
+    /// Minimum depth to search.
+    ///
+    /// At 0 the search starts with the given paths, at 1 it's directory contents.
+    #[arg(long, default_value_t = usize::MIN)]
+    pub min_depth: usize,
+
+    /// Maximum depth to search.
+    ///
+    /// Only search entries up to and including the given depth, at 0 the search only includes the
+    /// given paths.
+    ///
+    /// To disable recursion specify 1.
+    #[arg(long, short = 'd', default_value_t = usize::MAX)]
+    pub max_depth: usize,
+}
+
+/// A glob pattern, translated to a regex.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    /// Regex the glob was translated to.
+    regex: Regex,
+    /// Should the glob be matched against the relative path instead of the file name.
+    is_path: bool,
+}
+
+impl Glob {
+    /// Check if the glob matches a path relative to an input directory.
+    fn is_match(&self, relative: &Path) -> bool {
+        if self.is_path {
+            self.regex.is_match(relative.as_os_str().as_bytes())
+        } else {
+            relative
+                .file_name()
+                .is_some_and(|name| self.regex.is_match(name.as_bytes()))
+        }
+    }
+}
+
+/// Parse a glob by translating it to an anchored regex.
+///
+/// # Errors
+/// If the translated regex cannot be compiled, such as for invalid sets.
+fn glob(pattern: &str) -> Result<Glob, ::regex::Error> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(chr) = chars.next() {
+        match chr {
+            '*' if chars.next_if_eq(&'*').is_some() => {
+                if chars.next_if_eq(&'/').is_some() {
+                    regex.push_str("(?s-u:.*/)?");
+                } else {
+                    regex.push_str("(?s-u:.)*");
+                }
+            }
+            '*' => regex.push_str("(?-u:[^/])*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut set = String::new();
+                let mut closed = false;
+                if chars.next_if_eq(&'!').is_some() {
+                    set.push('^');
+                }
+                if let Some(chr) = chars.next_if_eq(&']') {
+                    set.push_str(&::regex::escape(&chr.to_string()));
+                }
+                for chr in chars.by_ref() {
+                    match chr {
+                        ']' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' | '[' | '&' | '~' | '^' => {
+                            set.push('\\');
+                            set.push(chr);
+                        }
+                        _ => set.push(chr),
+                    }
+                }
+                if !closed {
+                    return Err(::regex::Error::Syntax(format!(
+                        "unclosed set in glob '{pattern}'"
+                    )));
+                }
+                regex.push('[');
+                regex.push_str(&set);
+                regex.push(']');
+            }
+            _ => regex.push_str(&::regex::escape(chr.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+
+    Ok(Glob {
+        regex: Regex::new(&regex)?,
+        is_path: pattern.contains('/'),
+    })
+}
+
+/// Check if the name of an entry starts with a dot, the given paths are never hidden.
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().as_bytes().starts_with(b".")
+}
+
+impl Filter {
+    /// Check if a file at path, relative to its input directory, should be compiled.
+    pub fn is_match(&self, path: &Path, relative: &Path) -> bool {
+        let bytes = path.as_os_str().as_bytes();
+        (self.regex.is_empty() || self.regex.iter().any(|re| re.is_match(bytes)))
+            && !self.exclude_regex.iter().any(|re| re.is_match(bytes))
+            && (self.glob.is_empty() || self.glob.iter().any(|glob| glob.is_match(relative)))
+            && !self.exclude_glob.iter().any(|glob| glob.is_match(relative))
+            && (self.ext.is_empty()
+                || relative.extension().is_some_and(|ext| {
+                    self.ext.iter().any(|allowed| {
+                        let allowed = allowed.as_bytes();
+                        allowed
+                            .strip_prefix(b".")
+                            .unwrap_or(allowed)
+                            .eq_ignore_ascii_case(ext.as_bytes())
+                    })
+                }))
+    }
+
+    /// Walk root, yielding the regular files which should be compiled in file name order.
+    pub fn files<'a>(&'a self, root: &'a Path) -> impl 'a + Iterator<Item = PathBuf> {
+        WalkDir::new(root)
+            .sort_by_file_name()
+            .min_depth(self.min_depth)
+            .max_depth(self.max_depth)
+            .into_iter()
+            .filter_entry(|entry| self.hidden == HiddenBehaviour::Include || !is_hidden(entry))
+            .filter_map(|entry| {
+                entry
+                    .inspect_err(|err| ::log::warn!("could not get dir entry, {err}"))
+                    .ok()
+            })
+            .filter_map(move |entry| {
+                let path = entry.path();
+                (entry.metadata().ok()?.is_file()
+                    && self.is_match(path, path.strip_prefix(root).unwrap_or(path)))
+                .then(|| entry.into_path())
+            })
+    }
+}
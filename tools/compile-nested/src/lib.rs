@@ -4,21 +4,68 @@ use ::std::{
     collections::HashSet,
     ffi::OsString,
     fs,
-    hash::RandomState,
+    io::{self, Write},
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
-use ::clap::Parser;
+use ::clap::{Args, Parser, Subcommand};
 use ::color_eyre::{Section, eyre::eyre};
-use ::itertools::Itertools;
-use ::rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use ::walkdir::WalkDir;
+use ::rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+
+use crate::{
+    collision::OnCollision,
+    entry::Entry,
+    filter::Filter,
+    link_mode::LinkMode,
+    manifest::Manifest,
+    name::Namer,
+    sync::{Action, Origin, stale},
+    uncompile::Uncompile,
+};
+
+mod collision;
+mod entry;
+mod filter;
+mod link_mode;
+mod manifest;
+mod name;
+mod sync;
+mod uncompile;
 
 /// Application to compile nested directory contents into a single directory.
 #[derive(Debug, Parser)]
-#[command(author, version, long_about = None)]
+#[command(
+    author,
+    version,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Cli {
+    /// Inverse operation to use instead of compiling.
+    #[command(subcommand)]
+    subcmd: Option<Subcmd>,
+    /// Compile arguments.
+    #[command(flatten)]
+    compile: Option<Compile>,
+    /// Which files to compile, kept out of compile arguments as they would no longer be
+    /// detected as present if they contained a flattened struct.
+    #[command(flatten)]
+    filter: Filter,
+}
+
+/// Operations other than compiling.
+#[derive(Debug, Subcommand)]
+enum Subcmd {
+    /// Restore the nested structure of a compiled directory.
+    Uncompile(Uncompile),
+}
+
+/// Compile nested directory contents into a single directory.
+#[derive(Debug, Args)]
+struct Compile {
     /// Where to compile files to.
     outdir: PathBuf,
     /// What directory/ies to compile. If left out current directory is used.
@@ -26,21 +73,92 @@ pub struct Cli {
     /// Use provided value to separate path components.
     #[arg(long, short, default_value = "_")]
     sep: OsString,
-    /// Use symlinks instead of hardlinks.
-    #[arg(long)]
+    /// How to place files in outdir.
+    #[arg(long, short, value_enum, default_value_t)]
+    mode: LinkMode,
+    /// Use symlinks instead of hardlinks, same as '--mode=symlink'.
+    #[arg(long, conflicts_with = "mode")]
     symlink: bool,
+    /// What to do when multiple files are given the same name.
+    ///
+    /// Collisions are detected before anything is linked, and each is reported with the files
+    /// involved. Files are ordered by input directory and then by path when deciding which one
+    /// is first.
+    #[arg(long, short, value_enum, default_value_t)]
+    on_collision: OnCollision,
+    /// Write a manifest mapping each name in outdir to the input directory and path of the
+    /// file it was compiled from, for use with uncompile.
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+    /// Name files using a format string instead of joining path components with 'sep'.
+    ///
+    /// The keys 'dir', the directories of the path relative to the input directory joined
+    /// with 'sep', 'dir[N]', the Nth of those directories with negative values counting from
+    /// the end, 'root', the index of the input directory, and 'counter', a running count of
+    /// files, are available. As are the keys 'path', 'name', 'stem', 'ext', 'parent',
+    /// 'parent[N]' and 'component[N]' of the relative path.
+    ///
+    /// Parts a path does not have fail to look up, use a condition such as '{ext:-}' for
+    /// them to be empty.
+    #[arg(long, short, value_name = "TEMPLATE")]
+    name: Option<OsString>,
+    /// Keep outdir in sync with the input directories.
+    ///
+    /// Files of outdir which no longer correspond to an input file are removed, files which
+    /// are already linked correctly are skipped, and any others are replaced.
+    ///
+    /// Only files known to have been compiled are removed or replaced, those listed in the
+    /// manifest of a previous run if '--manifest' is given and it exists, and otherwise symlinks
+    /// into the input directories and hardlinks of files in them. Other files are left with a
+    /// warning.
+    #[arg(long)]
+    sync: bool,
+    /// Print what would be done, and a summary, without changing anything.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 impl ::file_suite_common::Run for Cli {
     type Error = ::color_eyre::Report;
 
     fn run(self) -> color_eyre::Result<()> {
+        match (self.subcmd, self.compile) {
+            (Some(Subcmd::Uncompile(uncompile)), _) => uncompile.run(),
+            (None, Some(compile)) => compile.run(&self.filter),
+            (None, None) => Err(eyre!("no outdir given")),
+        }
+    }
+}
+
+/// Get the absolute path of a file which might not exist, by canonicalizing its parent.
+fn absolute_file(path: &Path) -> Option<PathBuf> {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+impl Compile {
+    /// Compile the files of the input directories passing filter.
+    ///
+    /// # Errors
+    /// If the input or output directories cannot be used, or a collision is an error.
+    fn run(self, filter: &Filter) -> color_eyre::Result<()> {
         let Self {
             outdir,
             indir,
             sep,
+            mode,
             symlink,
+            on_collision,
+            manifest,
+            name,
+            sync,
+            dry_run,
         } = self;
+        let namer = Namer::new(name.as_deref(), sep.clone())?;
+        let mode = if symlink { LinkMode::Symlink } else { mode };
 
         let indir = if indir.is_empty() {
             Vec::from([::std::env::current_dir()
@@ -49,91 +167,183 @@ impl ::file_suite_common::Run for Cli {
             indir
         };
 
-        let inset = HashSet::<_, RandomState>::from_iter(indir.into_iter().filter_map(|path| {
-            path.canonicalize()
-                .inspect_err(|err| {
-                    ::log::warn!(
-                        "could not canonicalize '{path}, {err}'",
-                        path = path.display()
-                    );
-                })
-                .ok()
-        }));
+        let mut seen = HashSet::new();
+        let roots = indir
+            .into_iter()
+            .filter_map(|path| {
+                path.canonicalize()
+                    .inspect_err(|err| {
+                        ::log::warn!(
+                            "could not canonicalize '{path}, {err}'",
+                            path = path.display()
+                        );
+                    })
+                    .ok()
+            })
+            .filter(|root| seen.insert(root.clone()))
+            .collect::<Vec<_>>();
 
-        let file_paths = inset
+        let sources = roots
             .par_iter()
-            .flat_map_iter(|dir| {
-                let dir = Arc::<Path>::from(dir.as_path());
-                WalkDir::new(&dir)
-                    .into_iter()
-                    .filter_map(|entry| {
-                        entry
-                            .inspect_err(|err| ::log::warn!("could not get dir entry, {err}"))
-                            .ok()
-                            .and_then(|entry| {
-                                entry
-                                    .metadata()
-                                    .ok()?
-                                    .is_file()
-                                    .then_some(entry.into_path())
-                            })
-                    })
-                    .map(move |e| (Arc::clone(&dir), e))
+            .enumerate()
+            .flat_map_iter(|(root_idx, root)| filter.files(root).map(move |src| (root_idx, src)))
+            .collect::<Vec<_>>();
+
+        let entries = sources
+            .into_iter()
+            .enumerate()
+            .filter_map(|(counter, (root_idx, src))| {
+                Entry::new(root_idx, &roots[root_idx], src, &namer, counter)
             })
             .collect::<Vec<_>>();
 
+        let entries = on_collision.resolve(entries, &sep)?;
+
+        if dry_run {
+            let outdir = outdir.canonicalize().unwrap_or(outdir);
+            return Self::dry_run(
+                &outdir,
+                &entries,
+                &roots,
+                mode,
+                on_collision,
+                sync,
+                manifest,
+            );
+        }
+
         fs::create_dir_all(&outdir).map_err(|err| {
             eyre!(err).note(format!(
                 "are there sufficient permissions to create '{outdir}' if it is missing?",
                 outdir = outdir.display()
             ))
         })?;
+        let outdir = outdir.canonicalize()?;
+        let manifest = manifest
+            .map(|path| {
+                absolute_file(&path).ok_or_else(|| {
+                    eyre!("could not resolve manifest path '{}'", path.display())
+                        .suggestion("does the directory of the manifest exist?")
+                })
+            })
+            .transpose()?;
 
-        file_paths.into_par_iter().for_each(|(root, entry)| {
-            let root = AsRef::<Path>::as_ref(&root);
-            let entry = entry.as_path();
+        let origin = sync.then(|| Origin::new(manifest.as_deref(), &roots));
+        if let Some(origin) = &origin {
+            let expected = entries
+                .iter()
+                .map(|entry| entry.name.as_os_str())
+                .collect::<HashSet<_>>();
+            for path in stale(&outdir, &expected, manifest.as_deref(), origin)? {
+                match fs::remove_file(&path) {
+                    Ok(()) => ::log::info!("pruned '{path}'", path = path.display()),
+                    Err(err) => {
+                        ::log::warn!("could not prune '{path}', {err}", path = path.display())
+                    }
+                }
+            }
+        }
 
-            let Ok(relative) = entry.strip_prefix(root).inspect_err(|err| {
-                ::log::warn!(
-                    "could not stip prefix '{root}' from '{entry}', {err}",
-                    root = root.display(),
-                    entry = entry.display()
-                )
-            }) else {
-                return;
-            };
-
-            let new_name = Itertools::intersperse(
-                relative.components().filter_map(|comp| match comp {
-                    ::std::path::Component::Normal(os_str) => Some(os_str),
-                    _ => None,
-                }),
-                sep.as_os_str(),
-            )
-            .fold(OsString::new(), |mut s, v| {
-                s.push(v);
-                s
-            });
-
-            let new_path = outdir.join(new_name);
-
-            if symlink {
-                if let Err(err) = ::symlink::symlink_file(entry, &new_path) {
-                    ::log::warn!(
-                        "could not create symlink '{new_path}' -> '{entry}', {err}",
-                        entry = entry.display(),
-                        new_path = new_path.display()
-                    );
+        let replace = on_collision == OnCollision::Overwrite;
+        let linked = entries.into_par_iter().filter_map(|entry| {
+            let new_path = outdir.join(&entry.name);
+            let src = entry.src.as_path();
+
+            match Action::decide(src, &new_path, mode, replace, origin.as_ref()) {
+                Action::Keep => return Some(entry),
+                Action::Replace => {
+                    if let Err(err) = fs::remove_file(&new_path) {
+                        ::log::warn!(
+                            "could not remove '{new_path}', {err}",
+                            new_path = new_path.display()
+                        );
+                        return None;
+                    }
                 }
-            } else if let Err(err) = fs::hard_link(entry, &new_path) {
+                Action::Link => {}
+            }
+
+            if let Err(err) = mode.link(src, &new_path) {
                 ::log::warn!(
-                    "could not create hardlink '{new_path}' -> '{entry}', {err}",
+                    "could not create {mode} '{new_path}' -> '{src}', {err}",
+                    mode = mode.name(),
                     new_path = new_path.display(),
-                    entry = entry.display()
+                    src = src.display()
                 );
+                return None;
             }
+            Some(entry)
         });
 
+        if let Some(manifest) = manifest {
+            let linked = linked.collect::<Vec<_>>();
+            Manifest::new(roots, linked).write(&manifest)?;
+        } else {
+            linked.for_each(drop);
+        }
+
+        Ok(())
+    }
+
+    /// Print what would be done to outdir without changing anything, followed by a summary.
+    ///
+    /// # Errors
+    /// If outdir exists and cannot be read, or if printing fails.
+    fn dry_run(
+        outdir: &Path,
+        entries: &[Entry],
+        roots: &[PathBuf],
+        mode: LinkMode,
+        on_collision: OnCollision,
+        sync: bool,
+        manifest: Option<PathBuf>,
+    ) -> color_eyre::Result<()> {
+        let mut stdout = io::stdout().lock();
+        let (mut link, mut replace_count, mut keep) = (0usize, 0usize, 0usize);
+        let replace = on_collision == OnCollision::Overwrite;
+        let origin = sync.then(|| Origin::new(manifest.as_deref(), roots));
+        for entry in entries {
+            let action = Action::decide(
+                &entry.src,
+                &outdir.join(&entry.name),
+                mode,
+                replace,
+                origin.as_ref(),
+            );
+            *match action {
+                Action::Link => &mut link,
+                Action::Replace => &mut replace_count,
+                Action::Keep => &mut keep,
+            } += 1;
+            writeln!(
+                stdout,
+                "{action}\t{name}\t{src}",
+                name = entry.name.display(),
+                src = entry.src.display()
+            )?;
+        }
+
+        let stale = if let Some(origin) = &origin
+            && outdir.is_dir()
+        {
+            let expected = entries
+                .iter()
+                .map(|entry| entry.name.as_os_str())
+                .collect::<HashSet<_>>();
+            let exclude = manifest.as_deref().and_then(absolute_file);
+            stale(outdir, &expected, exclude.as_deref(), origin)?
+        } else {
+            Vec::new()
+        };
+        for path in &stale {
+            writeln!(stdout, "prune\t{}", path.display())?;
+        }
+
+        writeln!(
+            stdout,
+            "would link {link}, replace {replace_count}, keep {keep} and prune {prune} files",
+            prune = stale.len()
+        )?;
         Ok(())
     }
 }
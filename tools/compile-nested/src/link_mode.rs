@@ -0,0 +1,177 @@
+//! [LinkMode] implementation.
+
+use ::std::{
+    fs::{self, File},
+    io,
+    os::{
+        fd::AsFd,
+        unix::fs::{MetadataExt, PermissionsExt},
+    },
+    path::{Component, Path, PathBuf},
+};
+
+use ::clap::ValueEnum;
+
+/// How files are placed in the output directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LinkMode {
+    /// Reflink files, sharing data until either file is modified, falls back to copy.
+    Reflink,
+    /// Copy files.
+    Copy,
+    /// Hardlink files.
+    #[default]
+    Hardlink,
+    /// Symlink files using absolute paths.
+    Symlink,
+    /// Symlink files using paths relative to the output directory.
+    RelativeSymlink,
+}
+
+impl LinkMode {
+    /// Name of mode used in messages.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Reflink => "reflink",
+            Self::Copy => "copy",
+            Self::Hardlink => "hardlink",
+            Self::Symlink => "symlink",
+            Self::RelativeSymlink => "relative symlink",
+        }
+    }
+
+    /// Mode to use if this mode fails.
+    pub const fn fallback(self) -> Option<Self> {
+        match self {
+            Self::Reflink => Some(Self::Copy),
+            _ => None,
+        }
+    }
+
+    /// Check if src is already placed at dest as this mode would place it.
+    ///
+    /// Hardlinks need to be the same file, and symlinks need to have the expected target.
+    /// Copies and reflinks need to be regular files of the same size as src, modified no
+    /// earlier than it.
+    pub fn is_placed(self, src: &Path, dest: &Path) -> bool {
+        match self {
+            Self::Hardlink => match (fs::metadata(src), fs::symlink_metadata(dest)) {
+                (Ok(src), Ok(dest)) => (src.dev(), src.ino()) == (dest.dev(), dest.ino()),
+                _ => false,
+            },
+            Self::Symlink => fs::read_link(dest).is_ok_and(|target| target == src),
+            Self::RelativeSymlink => dest.parent().is_some_and(|parent| {
+                fs::read_link(dest).is_ok_and(|target| target == relative_path(parent, src))
+            }),
+            Self::Reflink | Self::Copy => match (fs::metadata(src), fs::symlink_metadata(dest)) {
+                (Ok(src), Ok(dest)) => {
+                    dest.is_file()
+                        && dest.len() == src.len()
+                        && matches!(
+                            (src.modified(), dest.modified()),
+                            (Ok(src), Ok(dest)) if dest >= src
+                        )
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Place src at dest, trying the fallback chain of the mode if it fails, returning the mode
+    /// which succeeded.
+    ///
+    /// Src is expected to be absolute, and dest to have an absolute parent for relative
+    /// symlinks.
+    ///
+    /// # Errors
+    /// If the last mode of the chain fails, or if dest exists.
+    pub fn link(self, src: &Path, dest: &Path) -> io::Result<Self> {
+        let mut mode = self;
+        loop {
+            match mode.link_once(src, dest) {
+                Ok(()) => return Ok(mode),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(err),
+                Err(err) => {
+                    let Some(fallback) = mode.fallback() else {
+                        return Err(err);
+                    };
+                    ::log::debug!(
+                        "could not {mode} '{src}', falling back to {fallback}, {err}",
+                        mode = mode.name(),
+                        fallback = fallback.name(),
+                        src = src.display(),
+                    );
+                    mode = fallback;
+                }
+            }
+        }
+    }
+
+    /// Place src at dest using only this mode.
+    ///
+    /// # Errors
+    /// If the file cannot be placed.
+    fn link_once(self, src: &Path, dest: &Path) -> io::Result<()> {
+        match self {
+            Self::Reflink => reflink(src, dest),
+            Self::Copy => copy(src, dest),
+            Self::Hardlink => fs::hard_link(src, dest),
+            Self::Symlink => ::symlink::symlink_file(src, dest),
+            Self::RelativeSymlink => {
+                let parent = dest
+                    .parent()
+                    .ok_or_else(|| io::Error::other("link path has no parent"))?;
+                ::symlink::symlink_file(relative_path(parent, src), dest)
+            }
+        }
+    }
+}
+
+/// Reflink src to a new file at dest.
+///
+/// # Errors
+/// If src cannot be opened or the reflink cannot be created.
+fn reflink(src: &Path, dest: &Path) -> io::Result<()> {
+    let src = File::open(src)?;
+    let mode = src.metadata()?.permissions().mode();
+    ::reflink_at::reflink_at(
+        None,
+        dest,
+        src.as_fd(),
+        ::reflink_at::Mode::from_bits_truncate(mode),
+        ::reflink_at::OnExists::CreateNewOnly,
+    )
+    .map_err(|err| match err {
+        ::reflink_at::ReflinkAtError::Errno(errno) => io::Error::from(errno),
+        err => io::Error::other(err),
+    })?;
+    Ok(())
+}
+
+/// Copy src to a new file at dest, keeping permissions.
+///
+/// # Errors
+/// If src cannot be read, dest exists or cannot be written.
+fn copy(src: &Path, dest: &Path) -> io::Result<()> {
+    let mut src = File::open(src)?;
+    let mut dest = File::create_new(dest)?;
+    io::copy(&mut src, &mut dest)?;
+    dest.set_permissions(src.metadata()?.permissions())
+}
+
+/// Get the path of to relative to the directory from, both of which should be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let mut from = from.components().peekable();
+    let mut to = to.components().peekable();
+    while let (Some(a), Some(b)) = (from.peek(), to.peek())
+        && a == b
+    {
+        from.next();
+        to.next();
+    }
+
+    from.filter(|comp| matches!(comp, Component::Normal(..)))
+        .map(|_| Component::ParentDir)
+        .chain(to)
+        .collect()
+}
@@ -0,0 +1,172 @@
+//! [Manifest] implementation.
+
+use ::std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::BufReader,
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Component, Path, PathBuf},
+};
+
+use ::color_eyre::eyre::eyre;
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::entry::Entry;
+
+/// A path as stored in a manifest, a string if it is valid utf-8 and an array of bytes otherwise.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawPath<S, B> {
+    /// Path which is valid utf-8.
+    Str(S),
+    /// Path which is not valid utf-8.
+    Bytes(B),
+}
+
+impl<'a> RawPath<&'a str, &'a [u8]> {
+    /// Get the representation of a path.
+    fn new(path: &'a Path) -> Self {
+        let bytes = path.as_os_str().as_bytes();
+        str::from_utf8(bytes).map_or(Self::Bytes(bytes), Self::Str)
+    }
+}
+
+impl From<RawPath<String, Vec<u8>>> for PathBuf {
+    fn from(value: RawPath<String, Vec<u8>>) -> Self {
+        match value {
+            RawPath::Str(path) => PathBuf::from(path),
+            RawPath::Bytes(path) => PathBuf::from(OsString::from_vec(path)),
+        }
+    }
+}
+
+/// Serialize a path as a [RawPath].
+///
+/// # Errors
+/// If the serializer fails.
+fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    RawPath::new(path).serialize(serializer)
+}
+
+/// Deserialize a path from a [RawPath].
+///
+/// # Errors
+/// If the value is neither a string nor an array of bytes.
+fn deserialize_path<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    RawPath::<String, Vec<u8>>::deserialize(deserializer).map(PathBuf::from)
+}
+
+/// Serialize paths as [RawPath] values.
+///
+/// # Errors
+/// If the serializer fails.
+fn serialize_paths<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(paths.iter().map(|path| RawPath::new(path)))
+}
+
+/// Deserialize paths from [RawPath] values.
+///
+/// # Errors
+/// If the value is not an array of strings or arrays of bytes.
+fn deserialize_paths<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    Vec::<RawPath<String, Vec<u8>>>::deserialize(deserializer)
+        .map(|paths| paths.into_iter().map(PathBuf::from).collect())
+}
+
+/// Mapping of compiled names to the files they were compiled from, stored as json.
+///
+/// Paths are stored as strings when they are valid utf-8, and as arrays of bytes otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Input directories, referred to by index.
+    #[serde(
+        serialize_with = "serialize_paths",
+        deserialize_with = "deserialize_paths"
+    )]
+    pub roots: Vec<PathBuf>,
+    /// Compiled files.
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A compiled file of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Name of file in output directory.
+    #[serde(
+        serialize_with = "serialize_path",
+        deserialize_with = "deserialize_path"
+    )]
+    pub name: PathBuf,
+    /// Index of the input directory of the source in roots.
+    pub root: usize,
+    /// Path of source relative to the input directory.
+    #[serde(
+        serialize_with = "serialize_path",
+        deserialize_with = "deserialize_path"
+    )]
+    pub path: PathBuf,
+}
+
+impl ManifestEntry {
+    /// Check that the name is a single component and that the path stays within the root.
+    pub fn is_contained(&self) -> bool {
+        let is_normal = |comp| matches!(comp, Component::Normal(..));
+        self.name.components().count() == 1
+            && self.name.components().all(is_normal)
+            && self.path.components().all(is_normal)
+    }
+}
+
+impl Manifest {
+    /// Create a manifest from input directories and the entries compiled from them.
+    pub fn new(roots: Vec<PathBuf>, entries: impl IntoIterator<Item = Entry>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|entry| ManifestEntry {
+                name: PathBuf::from(entry.name),
+                root: entry.root_idx,
+                path: entry.relative,
+            })
+            .collect();
+        Self { roots, entries }
+    }
+
+    /// Read a manifest from a file.
+    ///
+    /// # Errors
+    /// If the file cannot be opened or is not a valid manifest.
+    pub fn read(path: &Path) -> ::color_eyre::Result<Self> {
+        let file = File::open(path)
+            .map_err(|err| eyre!("could not open manifest '{}', {err}", path.display()))?;
+        ::serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| eyre!("could not read manifest '{}', {err}", path.display()))
+    }
+
+    /// Write manifest to a file.
+    ///
+    /// The manifest is written to a temporary file next to path which is then renamed, such that
+    /// an existing manifest is only ever replaced by a complete one.
+    ///
+    /// # Errors
+    /// If the manifest cannot be serialized, or the file cannot be written.
+    pub fn write(&self, path: &Path) -> ::color_eyre::Result<()> {
+        let mut json = ::serde_json::to_vec_pretty(self)
+            .map_err(|err| eyre!("could not serialize manifest '{}', {err}", path.display()))?;
+        json.push(b'\n');
+
+        let name = path
+            .file_name()
+            .ok_or_else(|| eyre!("manifest path '{}' has no file name", path.display()))?;
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(OsStr::new(".tmp"));
+        let tmp = path.with_file_name(tmp_name);
+
+        fs::write(&tmp, &json)
+            .and_then(|()| fs::rename(&tmp, path))
+            .map_err(|err| {
+                _ = fs::remove_file(&tmp);
+                eyre!("could not write manifest '{}', {err}", path.display())
+            })
+    }
+}
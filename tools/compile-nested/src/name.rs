@@ -0,0 +1,162 @@
+//! [Namer] implementation.
+
+use ::std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Component, Path},
+};
+
+use ::color_eyre::{Report, Section, eyre::eyre};
+use ::parse_fmt::{
+    FormatError, diagnostic::Diagnostic, display_bytes, lookup, lookup::PathLookupError,
+    template::Template,
+};
+
+/// Key of a name template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameKey {
+    /// Directory components of the relative path joined by the separator.
+    Dir,
+    /// A directory component of the relative path, negative indices counting from the end.
+    DirIdx(isize),
+    /// Index of the input directory.
+    Root,
+    /// Running counter of files.
+    Counter,
+    /// A part of the relative path, as resolved by [lookup::path].
+    Path(Vec<u8>),
+}
+
+impl NameKey {
+    /// Parse the key of a lookup.
+    ///
+    /// # Errors
+    /// If the key is not known.
+    fn parse(key: &[u8]) -> Result<Self, Report> {
+        Ok(match key {
+            b"dir" => Self::Dir,
+            b"root" => Self::Root,
+            b"counter" => Self::Counter,
+            _ => {
+                if let Some(idx) = key.strip_prefix(b"dir[").and_then(|k| k.strip_suffix(b"]")) {
+                    str::from_utf8(idx)
+                        .ok()
+                        .and_then(|idx| idx.parse().ok())
+                        .map(Self::DirIdx)
+                        .ok_or_else(|| {
+                            eyre!("could not parse index of key `{}`", display_bytes(key))
+                        })?
+                } else {
+                    match lookup::path(Path::new(""))(key) {
+                        Err(PathLookupError::UnknownKey(..)) => {
+                            return Err(eyre!("unknown key `{}`", display_bytes(key)));
+                        }
+                        Err(err @ PathLookupError::ParseIndex(..)) => return Err(eyre!("{err}")),
+                        _ => Self::Path(key.to_vec()),
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// How compiled files are named.
+#[derive(Debug, Clone)]
+pub enum Namer {
+    /// Join the components of the relative path with a separator.
+    Join(OsString),
+    /// Render a template, using the separator for the `dir` key.
+    Template(Template<NameKey>, OsString),
+}
+
+impl Namer {
+    /// Create a namer from an optional template, joining components with sep if there is none.
+    ///
+    /// # Errors
+    /// If the template cannot be parsed or uses unknown keys.
+    pub fn new(template: Option<&OsStr>, sep: OsString) -> ::color_eyre::Result<Self> {
+        let Some(template) = template else {
+            return Ok(Self::Join(sep));
+        };
+        let fmt = template.as_bytes();
+        match Template::parse_with(fmt, NameKey::parse) {
+            Ok(template) => Ok(Self::Template(template, sep)),
+            Err(FormatError::Parse(err)) => Err(eyre!(
+                "could not parse name template\n{}",
+                Diagnostic::new(fmt, &err)
+            )),
+            Err(FormatError::Lookup(err)) => Err(err.wrap_err("could not parse name template"))
+                .suggestion(concat!(
+                    "available keys are dir, dir[N], root, counter, path, name, stem, ext, ",
+                    "parent, parent[N] and component[N]"
+                )),
+        }
+    }
+
+    /// Name a file by its path relative to an input directory.
+    ///
+    /// # Errors
+    /// If a lookup of the template fails, or the name is not a single path component.
+    pub fn name(
+        &self,
+        root_idx: usize,
+        relative: &Path,
+        counter: usize,
+    ) -> ::color_eyre::Result<OsString> {
+        let normal = |comp| match comp {
+            Component::Normal(os_str) => Some(os_str),
+            _ => None,
+        };
+
+        let (template, sep) = match self {
+            Self::Join(sep) => {
+                return Ok(::itertools::Itertools::intersperse(
+                    relative.components().filter_map(normal),
+                    sep.as_os_str(),
+                )
+                .fold(OsString::new(), |mut s, v| {
+                    s.push(v);
+                    s
+                }));
+            }
+            Self::Template(template, sep) => (template, sep),
+        };
+
+        let dirs = relative
+            .parent()
+            .map(|parent| parent.components().filter_map(normal).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let path = lookup::path(relative);
+        let name = template.render::<Vec<u8>, _, _>(|key| match key {
+            NameKey::Dir => Ok(Cow::Owned(
+                ::itertools::Itertools::intersperse(
+                    dirs.iter().map(|dir| dir.as_bytes()),
+                    sep.as_bytes(),
+                )
+                .flatten()
+                .copied()
+                .collect(),
+            )),
+            NameKey::DirIdx(idx) => usize::try_from(*idx)
+                .ok()
+                .or_else(|| dirs.len().checked_sub(idx.unsigned_abs()))
+                .and_then(|idx| dirs.get(idx))
+                .map(|dir| Cow::Borrowed(dir.as_bytes()))
+                .ok_or_else(|| eyre!("path has no directory component {idx}")),
+            NameKey::Root => Ok(Cow::Owned(root_idx.to_string().into_bytes())),
+            NameKey::Counter => Ok(Cow::Owned(counter.to_string().into_bytes())),
+            NameKey::Path(key) => path(key).map_err(|err| eyre!("{err}")),
+        })?;
+
+        let name = OsString::from_vec(name);
+        let mut components = Path::new(&name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(..)), None) if !name.as_bytes().ends_with(b"/") => Ok(name),
+            _ => Err(eyre!(
+                "rendered name '{}' is not a file name",
+                name.display()
+            )),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Planning of changes to the output directory.
+
+use ::std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use ::walkdir::WalkDir;
+
+use crate::{link_mode::LinkMode, manifest::Manifest};
+
+/// What to do with the name of an entry in the output directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Place the source, nothing is expected to exist at the name.
+    Link,
+    /// Remove what exists at the name and place the source.
+    Replace,
+    /// The source is already placed at the name.
+    Keep,
+}
+
+impl Action {
+    /// Decide what to do with src and the name dest.
+    ///
+    /// Existing files are replaced if replace is true, or when syncing if origin knows them to
+    /// have been compiled, and kept when syncing if they are already placed as by mode. Any
+    /// other existing file is left for linking to fail on.
+    pub fn decide(
+        src: &Path,
+        dest: &Path,
+        mode: LinkMode,
+        replace: bool,
+        sync: Option<&Origin>,
+    ) -> Self {
+        if fs::symlink_metadata(dest).is_err() {
+            Self::Link
+        } else if sync.is_some() && mode.is_placed(src, dest) {
+            Self::Keep
+        } else if replace
+            || sync.is_some_and(|origin| {
+                dest.file_name()
+                    .is_some_and(|name| origin.is_compiled(dest, name))
+            })
+        {
+            Self::Replace
+        } else {
+            Self::Link
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Link => "link",
+            Self::Replace => "replace",
+            Self::Keep => "keep",
+        })
+    }
+}
+
+/// How files of the output directory are known to have been compiled, such that only those
+/// are pruned.
+#[derive(Debug)]
+pub enum Origin<'a> {
+    /// Names listed in the manifest of a previous run.
+    Manifest(HashSet<OsString>),
+    /// Symlinks into the input directories, and hardlinks of files in them.
+    Roots {
+        /// Input directories.
+        roots: &'a [PathBuf],
+        /// Device and inode of the files of the input directories, read when first needed.
+        inodes: OnceLock<HashSet<(u64, u64)>>,
+    },
+}
+
+impl<'a> Origin<'a> {
+    /// Use the manifest at path if there is one, and links into roots otherwise.
+    pub fn new(manifest: Option<&Path>, roots: &'a [PathBuf]) -> Self {
+        if let Some(path) = manifest.filter(|path| path.exists()) {
+            match Manifest::read(path) {
+                Ok(manifest) => {
+                    return Self::Manifest(
+                        manifest
+                            .entries
+                            .into_iter()
+                            .map(|entry| entry.name.into_os_string())
+                            .collect(),
+                    );
+                }
+                Err(err) => ::log::warn!("only pruning links into input directories, {err}"),
+            }
+        }
+        Self::Roots {
+            roots,
+            inodes: OnceLock::new(),
+        }
+    }
+
+    /// Check if the file at path, of outdir, is known to have been compiled.
+    fn is_compiled(&self, path: &Path, name: &OsStr) -> bool {
+        let (roots, inodes) = match self {
+            Self::Manifest(names) => return names.contains(name),
+            Self::Roots { roots, inodes } => (roots, inodes),
+        };
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            return false;
+        };
+
+        if meta.is_symlink() {
+            let Some(target) = fs::read_link(path)
+                .ok()
+                .and_then(|target| Some(normalize(&path.parent()?.join(target))))
+            else {
+                return false;
+            };
+            roots.iter().any(|root| target.starts_with(root))
+        } else if meta.is_file() && meta.nlink() > 1 {
+            inodes
+                .get_or_init(|| {
+                    roots
+                        .iter()
+                        .flat_map(|root| WalkDir::new(root).into_iter().filter_map(Result::ok))
+                        .filter_map(|entry| entry.metadata().ok())
+                        .filter(|meta| meta.is_file())
+                        .map(|meta| (meta.dev(), meta.ino()))
+                        .collect()
+                })
+                .contains(&(meta.dev(), meta.ino()))
+        } else {
+            false
+        }
+    }
+}
+
+/// Lexically resolve the `.` and `..` components of an absolute path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            comp => normal.push(comp),
+        }
+    }
+    normal
+}
+
+/// Find the files of outdir which are not expected, to be pruned.
+///
+/// Only files which origin knows to have been compiled are pruned, any others are left with a
+/// warning. Directories are never pruned, and neither is exclude, such that a manifest may be
+/// kept in the output directory.
+///
+/// # Errors
+/// If outdir cannot be read.
+pub fn stale(
+    outdir: &Path,
+    expected: &HashSet<&OsStr>,
+    exclude: Option<&Path>,
+    origin: &Origin,
+) -> io::Result<Vec<PathBuf>> {
+    let mut stale = Vec::new();
+    for entry in fs::read_dir(outdir)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                ::log::warn!("could not get dir entry, {err}");
+                continue;
+            }
+        };
+        let path = entry.path();
+        let name = entry.file_name();
+        if expected.contains(name.as_os_str()) || exclude == Some(path.as_path()) {
+            continue;
+        }
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            ::log::debug!("not pruning directory '{path}'", path = path.display());
+            continue;
+        }
+        if !origin.is_compiled(&path, &name) {
+            ::log::warn!(
+                "not pruning '{path}', it is not known to have been compiled",
+                path = path.display()
+            );
+            continue;
+        }
+        stale.push(path);
+    }
+    stale.sort_unstable();
+    Ok(stale)
+}
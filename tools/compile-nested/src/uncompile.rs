@@ -0,0 +1,277 @@
+//! [Uncompile] implementation.
+
+use ::std::{
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use ::clap::Args;
+use ::color_eyre::{Section, eyre::eyre};
+use ::rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{link_mode::LinkMode, manifest::Manifest};
+
+/// Restore a nested directory structure from a compiled directory.
+#[derive(Debug, Args)]
+pub struct Uncompile {
+    /// Compiled directory to restore files from.
+    flatdir: PathBuf,
+    /// Where to restore the nested structure to.
+    ///
+    /// Required without a manifest, if left out files are restored to the input directories
+    /// of the manifest.
+    #[arg(required_unless_present = "manifest")]
+    outdir: Option<PathBuf>,
+    /// Manifest written when compiling, mapping names to the files they were compiled from.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Split names on provided value when no manifest is given.
+    #[arg(long, short, default_value = "_", conflicts_with = "manifest")]
+    sep: OsString,
+    /// How to place files.
+    #[arg(long, short, value_enum, default_value_t)]
+    mode: LinkMode,
+    /// Replace existing files which are not the same file as the compiled one.
+    #[arg(long)]
+    overwrite: bool,
+}
+
+/// Get the path of name split by sep, skipping empty components.
+fn split_name(name: &OsStr, sep: &OsStr) -> PathBuf {
+    let (name, sep) = (name.as_bytes(), sep.as_bytes());
+    if sep.is_empty() {
+        return PathBuf::from(OsStr::from_bytes(name));
+    }
+
+    let mut path = PathBuf::new();
+    let mut rest = name;
+    while let Some(idx) = rest.windows(sep.len()).position(|window| window == sep) {
+        if idx != 0 {
+            path.push(OsStr::from_bytes(&rest[..idx]));
+        }
+        rest = &rest[idx + sep.len()..];
+    }
+    if !rest.is_empty() {
+        path.push(OsStr::from_bytes(rest));
+    }
+    path
+}
+
+/// Resolve any symlinks of a file in the compiled directory, such that a directory compiled
+/// using symlinks has the files they point to restored, not the links.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path)
+        .inspect_err(|err| ::log::warn!("could not resolve '{path}', {err}", path = path.display()))
+        .ok()
+}
+
+/// Restore src to dest, skipping it if dest already is the same file.
+fn restore(src: &Path, dest: &Path, mode: LinkMode, overwrite: bool) {
+    let src_meta = match fs::metadata(src) {
+        Ok(meta) => meta,
+        Err(err) => {
+            ::log::warn!("could not read '{src}', {err}", src = src.display());
+            return;
+        }
+    };
+
+    if let Ok(dest_meta) = fs::metadata(dest) {
+        if (dest_meta.dev(), dest_meta.ino()) == (src_meta.dev(), src_meta.ino()) {
+            ::log::debug!("'{dest}' is already restored", dest = dest.display());
+            return;
+        }
+        if !overwrite {
+            ::log::warn!(
+                "not restoring '{src}', '{dest}' exists",
+                src = src.display(),
+                dest = dest.display()
+            );
+            return;
+        }
+        if let Err(err) = fs::remove_file(dest) {
+            ::log::warn!("could not remove '{dest}', {err}", dest = dest.display());
+            return;
+        }
+    }
+
+    if let Some(parent) = dest.parent()
+        && let Err(err) = fs::create_dir_all(parent)
+    {
+        ::log::warn!(
+            "could not create '{parent}', {err}",
+            parent = parent.display()
+        );
+        return;
+    }
+
+    if let Err(err) = mode.link(src, dest) {
+        ::log::warn!(
+            "could not create {mode} '{dest}' -> '{src}', {err}",
+            mode = mode.name(),
+            dest = dest.display(),
+            src = src.display()
+        );
+    }
+}
+
+impl ::file_suite_common::Run for Uncompile {
+    type Error = ::color_eyre::Report;
+
+    fn run(self) -> Result<(), Self::Error> {
+        let Self {
+            flatdir,
+            outdir,
+            manifest,
+            sep,
+            mode,
+            overwrite,
+        } = self;
+
+        let flatdir = flatdir.canonicalize().map_err(|err| {
+            eyre!("could not canonicalize '{}', {err}", flatdir.display())
+                .suggestion("does the compiled directory exist?")
+        })?;
+        let outdir = outdir
+            .map(|outdir| {
+                fs::create_dir_all(&outdir)?;
+                outdir.canonicalize()
+            })
+            .transpose()?;
+
+        let placements = if let Some(manifest) = manifest {
+            let Manifest { roots, entries } = Manifest::read(&manifest)?;
+            entries
+                .into_iter()
+                .filter_map(|entry| {
+                    if !entry.is_contained() {
+                        ::log::warn!(
+                            "skipping manifest entry '{name}', '{path}' is not contained",
+                            name = entry.name.display(),
+                            path = entry.path.display(),
+                        );
+                        return None;
+                    }
+                    let Some(root) = outdir.as_ref().or_else(|| roots.get(entry.root)) else {
+                        ::log::warn!(
+                            "skipping manifest entry '{name}', it has no input directory {root}",
+                            name = entry.name.display(),
+                            root = entry.root,
+                        );
+                        return None;
+                    };
+                    Some((resolve(&flatdir.join(&entry.name))?, root.join(&entry.path)))
+                })
+                .collect::<Vec<_>>()
+        } else {
+            let outdir = outdir.ok_or_else(|| eyre!("an outdir is needed without a manifest"))?;
+            fs::read_dir(&flatdir)?
+                .filter_map(|entry| {
+                    let entry = entry
+                        .inspect_err(|err| ::log::warn!("could not get dir entry, {err}"))
+                        .ok()?;
+                    let src = resolve(&entry.path())?;
+                    fs::metadata(&src).ok()?.is_file().then(|| {
+                        let dest = outdir.join(split_name(&entry.file_name(), &sep));
+                        (src, dest)
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        placements
+            .into_par_iter()
+            .for_each(|(src, dest)| restore(&src, &dest, mode, overwrite));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::{fs, path::Path};
+
+    use ::clap::Parser;
+    use ::file_suite_common::Run;
+
+    use crate::Cli;
+
+    /// Run the application with args.
+    ///
+    /// # Errors
+    /// If the args are invalid or the run fails.
+    fn run(args: &[&Path]) -> color_eyre::Result<()> {
+        Cli::try_parse_from(
+            ::std::iter::once(Path::new("compile-nested").as_os_str())
+                .chain(args.iter().map(|arg| arg.as_os_str())),
+        )?
+        .run()
+    }
+
+    /// Directories compiled using symlinks should have the files linked to restored.
+    ///
+    /// # Errors
+    /// If the test directory cannot be set up, or compiling or uncompiling fails.
+    ///
+    /// # Panics
+    /// If files are not compiled as symlinks or not restored with their contents.
+    #[test]
+    fn symlink_round_trip() -> color_eyre::Result<()> {
+        let dir = ::std::env::temp_dir().join(format!(
+            "compile-nested-symlink-round-trip-{}",
+            ::std::process::id()
+        ));
+        let (indir, flatdir, outdir) = (dir.join("in"), dir.join("flat"), dir.join("out"));
+        let manifest = dir.join("manifest.json");
+        fs::create_dir_all(indir.join("a"))?;
+        fs::write(indir.join("a/x"), "x")?;
+        fs::write(indir.join("y"), "y")?;
+
+        for mode in ["relative-symlink", "symlink"] {
+            let mode = Path::new(mode);
+            run(&[
+                Path::new("--mode"),
+                mode,
+                Path::new("--manifest"),
+                &manifest,
+                &flatdir,
+                &indir,
+            ])?;
+            assert_eq!(
+                fs::symlink_metadata(flatdir.join("a_x"))
+                    .map(|meta| meta.is_symlink())
+                    .ok(),
+                Some(true)
+            );
+
+            let by_manifest = outdir.join("manifest");
+            let by_sep = outdir.join("sep");
+            run(&[
+                Path::new("uncompile"),
+                Path::new("--manifest"),
+                &manifest,
+                &flatdir,
+                &by_manifest,
+            ])?;
+            run(&[Path::new("uncompile"), &flatdir, &by_sep])?;
+
+            for restored in [&by_manifest, &by_sep] {
+                assert_eq!(
+                    fs::read_to_string(restored.join("a/x")).ok(),
+                    Some("x".into())
+                );
+                assert_eq!(
+                    fs::read_to_string(restored.join("y")).ok(),
+                    Some("y".into())
+                );
+            }
+
+            fs::remove_dir_all(&flatdir)?;
+            fs::remove_dir_all(&outdir)?;
+        }
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
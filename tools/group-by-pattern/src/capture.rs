@@ -0,0 +1,151 @@
+//! [Capture] implementation.
+
+use ::chumsky::extra;
+
+use crate::Error;
+
+/// Capture group used by the lookup of a template.
+#[derive(Debug)]
+pub enum Capture<'a, I: ?Sized> {
+    /// Required capture by index.
+    Idx(usize),
+    /// Required capture by name.
+    Name(&'a I),
+    /// Optional capture by index.
+    IdxOpt(usize),
+    /// Optional capture by name.
+    NameOpt(&'a I),
+}
+
+impl<'a, I: ?Sized> Copy for Capture<'a, I> {}
+
+impl<'a, I: ?Sized> Clone for Capture<'a, I> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// Parser extra used by lookup parser.
+type Extra<'a> = extra::Err<::chumsky::error::Rich<'a, u8>>;
+
+/// Parser of the keys of lookups, numbering empty keys.
+#[derive(Debug, Default)]
+pub struct KeyParser {
+    /// Index of next empty key.
+    idx_counter: usize,
+}
+
+impl KeyParser {
+    /// Parse the key of a lookup.
+    ///
+    /// # Errors
+    /// If the key cannot be parsed or names a capture group which is not utf-8.
+    pub fn parse<'a>(&mut self, key: &'a [u8]) -> Result<Capture<'a, str>, Error> {
+        use ::chumsky::Parser;
+
+        let capture = Capture::lookup_parser()
+            .parse(key)
+            .into_result()
+            .map_err(|err| {
+                use ::std::fmt::Write as _;
+                let mut msg = String::new();
+
+                for err in err {
+                    write!(msg, "\n{err}").expect("write to string should succeed");
+                }
+
+                Error::ParseLookup {
+                    chunk: key.into(),
+                    msg,
+                }
+            })?;
+
+        match capture {
+            Capture::Name([]) => {
+                self.idx_counter += 1;
+                Ok(Capture::Idx(self.idx_counter - 1))
+            }
+            Capture::NameOpt([]) => {
+                self.idx_counter += 1;
+                Ok(Capture::IdxOpt(self.idx_counter - 1))
+            }
+            other => other.to_utf8(),
+        }
+    }
+}
+
+impl<'a> Capture<'a, [u8]> {
+    /// Get a parser for the key of a lookup.
+    fn lookup_parser() -> impl ::chumsky::Parser<'a, &'a [u8], Self, Extra<'a>> {
+        use ::chumsky::prelude::*;
+
+        let idx = || {
+            text::int(10).try_map(|s: &[u8], span| {
+                str::from_utf8(s)
+                    .ok()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .ok_or_else(|| Rich::custom(span, "capture index is too large"))
+            })
+        };
+        let num = idx().map(Capture::Idx);
+        let opt_num = idx().map(Capture::IdxOpt);
+        let name = any().repeated().to_slice().map(|s: &[u8]| Capture::Name(s));
+        let opt_name = any()
+            .repeated()
+            .to_slice()
+            .map(|s: &[u8]| Capture::NameOpt(s));
+
+        // Names not marked by '-' may not start with a digit, nor unmarked names with a marker,
+        // such that a key failing to parse as an index is not used as a name.
+        let unmarked = |start: &'static [u8]| {
+            none_of(start)
+                .ignored()
+                .or(end())
+                .then(any().repeated())
+                .to_slice()
+        };
+        let digits = b"0123456789";
+        let marks = b"0123456789?.#-";
+
+        let opt = opt_num.or(unmarked(digits).map(|s: &[u8]| Capture::NameOpt(s)));
+        let non_opt = num.or(unmarked(digits).map(|s: &[u8]| Capture::Name(s)));
+
+        // Indices are tried first, as an index failing to parse discards the errors of
+        // alternatives tried before it.
+        choice((
+            num,
+            just(b'?').ignore_then(opt),
+            just(b'.').ignore_then(non_opt),
+            just(b'#').ignore_then(choice((
+                num,
+                just(b'?').ignore_then(opt_num),
+                just(b'.').ignore_then(num),
+            ))),
+            just(b'-').ignore_then(choice((
+                just(b'?').ignore_then(opt_name),
+                just(b'.').ignore_then(name),
+                name,
+            ))),
+            unmarked(marks).map(|s: &[u8]| Capture::Name(s)),
+        ))
+    }
+
+    /// Convert names of capture groups to utf-8.
+    ///
+    /// # Errors
+    /// If the name is not utf-8.
+    fn to_utf8(self) -> Result<Capture<'a, str>, Error> {
+        Ok(match self {
+            Capture::Idx(idx) => Capture::Idx(idx),
+            Capture::Name(name) => Capture::Name(
+                str::from_utf8(name)
+                    .map_err(|err| Error::NonUtf8CaptureLookup(err, name.into()))?,
+            ),
+            Capture::IdxOpt(idx) => Capture::IdxOpt(idx),
+            Capture::NameOpt(name) => Capture::NameOpt(
+                str::from_utf8(name)
+                    .map_err(|err| Error::NonUtf8CaptureLookup(err, name.into()))?,
+            ),
+        })
+    }
+}
@@ -0,0 +1,175 @@
+//! [CommandTemplate] implementation.
+
+use ::std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    process::Command,
+};
+
+use ::parse_fmt::{FormatError, diagnostic::Diagnostic, template::Template};
+use ::regex::bytes::{Captures, Regex};
+use ::rustc_hash::FxHashSet;
+
+use crate::{
+    Error,
+    capture::{Capture, KeyParser},
+};
+
+/// Template used to create the command of a group.
+#[derive(Debug)]
+pub struct CommandTemplate<'a> {
+    /// Executable to run, missing if the command is empty.
+    exe: Option<&'a OsStr>,
+    /// Parsed arguments passed to executable.
+    args: Vec<Template<Capture<'a, str>>>,
+}
+
+impl<'a> CommandTemplate<'a> {
+    /// Parse a command template from an executable and a list of arguments.
+    ///
+    /// # Errors
+    /// If any argument cannot be parsed.
+    pub fn parse(command: &'a [OsString]) -> Result<Self, Error> {
+        let (exe, args) = match command {
+            [exe, args @ ..] => (Some(exe.as_os_str()), args),
+            [] => (None, command),
+        };
+
+        let args = args
+            .iter()
+            .map(|arg| {
+                let mut key_parser = KeyParser::default();
+                Template::parse_with(arg.as_bytes(), |key| key_parser.parse(key)).map_err(|err| {
+                    match err {
+                        FormatError::Parse(err) => Error::ParseFmt {
+                            arg: arg.display().to_string(),
+                            msg: format!("\n{}", Diagnostic::new(arg.as_bytes(), &err)),
+                        },
+                        FormatError::Lookup(err) => err,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { exe, args })
+    }
+
+    /// Validate that all captures used by the template exist for the given regex.
+    ///
+    /// # Errors
+    /// If a capture group index or name is used which does not exist in regex.
+    pub fn validate(&self, regex: &Regex, pattern: &str) -> Result<(), Error> {
+        let highest = regex.captures_len() - 1;
+        let groups = regex.capture_names().flatten().collect::<FxHashSet<_>>();
+
+        for arg in &self.args {
+            arg.validate(|capture| match *capture {
+                Capture::Idx(idx) | Capture::IdxOpt(idx) => idx <= highest,
+                Capture::Name(name) | Capture::NameOpt(name) => groups.contains(name),
+            })
+            .map_err(|capture| match *capture {
+                Capture::Idx(idx) | Capture::IdxOpt(idx) => Error::UnknownGroupIdx {
+                    idx,
+                    pattern: pattern.to_owned(),
+                    highest,
+                },
+                Capture::Name(name) | Capture::NameOpt(name) => Error::UnknownGroupName {
+                    name: name.to_owned(),
+                    pattern: pattern.to_owned(),
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a command for a group.
+    ///
+    /// If captures are missing, `{0}` resolves to the key.
+    ///
+    /// # Errors
+    /// If the command is empty or a required capture is missing.
+    pub fn build(
+        &self,
+        key: &OsStr,
+        captures: Option<&Captures>,
+        pattern: &str,
+    ) -> Result<Command, Error> {
+        let mut command = Command::new(self.exe.ok_or(Error::EmptyCommand)?);
+
+        let mut buf = Vec::<u8>::new();
+        for arg in &self.args {
+            buf.clear();
+            arg.render_to(&mut buf, |capture| {
+                let bytes = match *capture {
+                    Capture::Idx(0) if captures.is_none() => key.as_bytes(),
+                    Capture::Idx(idx) => captures
+                        .and_then(|captures| captures.get(idx))
+                        .ok_or_else(|| Error::MissingGroupIdx {
+                            idx,
+                            pattern: pattern.to_owned(),
+                        })?
+                        .as_bytes(),
+                    Capture::Name(name) => captures
+                        .and_then(|captures| captures.name(name))
+                        .ok_or_else(|| Error::MissingGroupName {
+                            name: name.to_owned(),
+                            pattern: pattern.to_owned(),
+                        })?
+                        .as_bytes(),
+                    Capture::IdxOpt(idx) => captures
+                        .and_then(|captures| captures.get(idx))
+                        .map_or(&[][..], |m| m.as_bytes()),
+                    Capture::NameOpt(name) => captures
+                        .and_then(|captures| captures.name(name))
+                        .map_or(&[][..], |m| m.as_bytes()),
+                };
+                Ok::<_, Error>(Cow::Borrowed(bytes))
+            })?;
+            command.arg(OsStr::from_bytes(&buf));
+        }
+
+        Ok(command)
+    }
+}
+
+/// Export the key, captures and input count of a group to the environment of a command.
+///
+/// If captures are missing, `GBP_CAP_0` is set to the key and all other captures are unset.
+pub fn export_env(
+    command: &mut Command,
+    regex: &Regex,
+    key: &OsStr,
+    captures: Option<&Captures>,
+    count: usize,
+) {
+    command
+        .env("GBP_KEY", key)
+        .env("GBP_COUNT", count.to_string());
+
+    for (idx, name) in regex.capture_names().enumerate() {
+        let idx_var = format!("GBP_CAP_{idx}");
+        let name_var = name.map(|name| format!("GBP_CAP_{name}"));
+        let value = match captures {
+            Some(captures) => captures
+                .get(idx)
+                .map(|r#match| OsStr::from_bytes(r#match.as_bytes())),
+            None => (idx == 0).then_some(key),
+        };
+
+        // Unset missing captures to not leak values from our own environment.
+        let Some(value) = value else {
+            command.env_remove(idx_var);
+            if let Some(name_var) = name_var {
+                command.env_remove(name_var);
+            }
+            continue;
+        };
+
+        command.env(idx_var, value);
+        if let Some(name_var) = name_var {
+            command.env(name_var, value);
+        }
+    }
+}
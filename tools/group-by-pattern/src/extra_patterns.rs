@@ -0,0 +1,66 @@
+//! [ExtraPatterns] implementation.
+
+use ::std::ffi::OsString;
+
+use ::clap::{Arg, ArgAction, ArgMatches, Args, Command, FromArgMatches, value_parser};
+
+/// Additional patterns, each with an optional command, kept separate per occurrence.
+#[derive(Debug, Clone, Default)]
+pub struct ExtraPatterns {
+    /// Regex followed by command arguments, for every occurrence.
+    pub patterns: Vec<Vec<OsString>>,
+}
+
+impl ExtraPatterns {
+    /// Id used for argument.
+    const ID: &str = "pattern";
+}
+
+impl FromArgMatches for ExtraPatterns {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let patterns = matches
+            .get_occurrences::<OsString>(Self::ID)
+            .map(|occurrences| {
+                occurrences
+                    .map(|occurrence| occurrence.cloned().collect())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self { patterns })
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+        Ok(())
+    }
+}
+
+impl Args for ExtraPatterns {
+    fn augment_args(cmd: Command) -> Command {
+        cmd.arg(
+            Arg::new(Self::ID)
+                .long(Self::ID)
+                .num_args(1..)
+                .value_terminator(";")
+                .value_names(["REGEX", "COMMAND"])
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(OsString))
+                .help("Additional pattern to try, in order, for inputs not matching any earlier pattern")
+                .long_help(
+                    "Additional pattern to try, in order, for inputs not matching any earlier pattern.\n\
+                    \n\
+                    The regex may be followed by a command to use for groups of the pattern, \
+                    if no command is given the default command is used. Every occurrence has to \
+                    be terminated by `;`.\n\
+                    \n\
+                    Groups are kept separate per pattern, the remainder may only be merged with \
+                    groups of the first pattern.",
+                ),
+        )
+    }
+
+    fn augment_args_for_update(cmd: Command) -> Command {
+        Self::augment_args(cmd)
+    }
+}
@@ -3,26 +3,54 @@
 use ::std::{
     borrow::Cow,
     ffi::{OsStr, OsString},
-    io::{BufRead, Write, stderr, stdout},
+    io::{BufRead, stdout},
     os::unix::ffi::{OsStrExt, OsStringExt},
-    process::{Command, ExitStatus, Stdio},
     str::Utf8Error,
+    sync::atomic::AtomicBool,
+    time::Duration,
 };
 
 use ::clap::{Parser, ValueHint};
 use ::rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use ::regex::bytes::Captures;
-use ::rustc_hash::FxHashSet;
-use ::smallvec::SmallVec;
-
-use crate::lookup_chunk::LookupChunk;
+use ::regex::bytes::{Captures, Regex, RegexBuilder};
+
+use crate::{
+    command_template::{CommandTemplate, export_env},
+    extra_patterns::ExtraPatterns,
+    match_on::MatchOn,
+    spawn::{Failures, Policy},
+    summary::{GroupSummary, SortBy},
+    walk::Walk,
+};
 
-mod lookup_chunk;
+mod capture;
+mod command_template;
+mod extra_patterns;
+mod match_on;
+mod spawn;
+mod summary;
+mod walk;
+
+/// Parse a duration in seconds, optionally suffixed by a unit.
+///
+/// # Errors
+/// If the duration is not a valid non-negative number of a known unit.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (num, scale) = [("ms", 0.001), ("s", 1.0), ("m", 60.0), ("h", 3600.0)]
+        .into_iter()
+        .find_map(|(suffix, scale)| value.strip_suffix(suffix).map(|num| (num, scale)))
+        .unwrap_or((value, 1.0));
+
+    num.trim()
+        .parse::<f64>()
+        .map_err(|err| err.to_string())
+        .and_then(|num| Duration::try_from_secs_f64(num * scale).map_err(|err| err.to_string()))
+}
 
 /// Group input by the result of a regex pattern.
 #[derive(Debug, Parser)]
 pub struct Cli {
-    /// Regex pattern to match.
+    /// Regex pattern to match, tried before any additional patterns.
     regex: String,
 
     /// Expect null separated/terminated input, and provide null separated output.
@@ -53,9 +81,45 @@ pub struct Cli {
     #[arg(long, short)]
     ignore_case: bool,
 
-    /// Group to add all inputs not matching the pattern to.
+    /// Part of input paths to match patterns against, the whole input is still passed to
+    /// commands.
+    ///
+    /// One of `path`, `basename`, `dirname`, `stem`, `ext` or `component=N`, where N is the index
+    /// of a path component, negative indices count from the end. Inputs without the given part
+    /// do not match any pattern.
+    #[arg(long, default_value = "path", value_name = "PART")]
+    match_on: MatchOn,
+
+    /// Stream output of commands line by line, prefixing every line with the group and a tab.
     ///
-    /// If the same as a pattern matched the remainder will be merged
+    /// Without this the output of a command is printed as a block once it exits.
+    #[arg(long)]
+    tag: bool,
+
+    /// Kill commands running for longer than the given duration.
+    ///
+    /// The duration is in seconds unless suffixed by `ms`, `s`, `m` or `h`.
+    #[arg(long, value_parser = parse_duration, value_name = "DURATION")]
+    timeout: Option<Duration>,
+
+    /// Amount of times to retry a failed command.
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    retries: usize,
+
+    /// Kill all running commands and skip remaining groups after the first failure.
+    ///
+    /// As with timeout, commands are run in their own process group such that any processes
+    /// they spawn may be killed with them.
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Run the commands of all groups regardless of failures, default.
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Group to add all inputs not matching any pattern to.
+    ///
+    /// If the same as a match of the first pattern the remainder will be merged
     /// with said group.
     ///
     /// If used without a value the empty string will be used for the group.
@@ -64,15 +128,59 @@ pub struct Cli {
     #[arg(long, require_equals = true, default_missing_value = "", num_args = 0..=1)]
     remainder: Option<OsString>,
 
+    /// Additional patterns.
+    #[command(flatten)]
+    pattern: ExtraPatterns,
+
+    /// Skip groups with fewer members than this.
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    min_members: usize,
+
+    /// Skip groups with more members than this.
+    #[arg(long, default_value_t = usize::MAX, value_name = "N")]
+    max_members: usize,
+
+    /// Print a table of groups, their member counts and total sizes instead of running commands.
+    ///
+    /// The table is sorted by the given field, the size is summed for members which are paths.
+    /// If used the command may be left out.
+    #[arg(
+        long,
+        value_enum,
+        require_equals = true,
+        num_args = 0..=1,
+        default_missing_value = "key",
+        value_name = "SORT_BY"
+    )]
+    summary: Option<SortBy>,
+
+    /// Reverse the sorting of the summary.
+    #[arg(long, requires = "summary")]
+    reverse: bool,
+
+    /// Walk configuration.
+    #[command(flatten)]
+    walk: Walk,
+
     /// Command to execute for group.
     ///
     /// Captures of a match of the group may be accessed using `{NAME}` or `{?NAME}` syntax,
     /// where the first case requires the capture and the second has it as optional, resolving to
-    /// the empty string if missing.
+    /// the empty string if missing. Captures may be followed by colon separated modifiers such as
+    /// `{1:0>3}` to pad, `{1:.8}` to truncate or `{NAME:upper}` to convert case. The last
+    /// modifier may be `-TEXT` to use TEXT when the capture is missing or empty, as in
+    /// `{NAME:-default}`, or `+TEXT` to use TEXT only when it is present and not empty.
     ///
     /// When remainder is used `{0}` will resolve to the remainder group name, which may be the
     /// empty string. If the remainder group is shared with another group, `{0}` will resolve to
     /// said capture of one of the matches of that group as would be otherwise.
+    ///
+    /// The same values are exported to the environment of the command, the group as `GBP_KEY`,
+    /// the amount of inputs as `GBP_COUNT` and captures as `GBP_CAP_<NAME>` and `GBP_CAP_<INDEX>`.
+    ///
+    /// Commands which exit unsuccessfully, time out or cannot be communicated with are listed in
+    /// a summary once all groups are done. Commands need not read their inputs from stdin, one
+    /// which exits before all inputs are written is not considered to have failed.
     #[arg(num_args = 1.., trailing_var_arg = true, value_hint = ValueHint::CommandWithArguments)]
     command: Vec<OsString>,
 }
@@ -86,6 +194,9 @@ pub enum Error {
     /// Could not read input.
     #[error("while reading input, {0}")]
     InputIO(#[source] ::std::io::Error),
+    /// Could not write output.
+    #[error("while writing output, {0}")]
+    OutputIO(#[source] ::std::io::Error),
     /// Could not parse format for arguments.
     #[error("could not parse format for argument `{arg}`{msg}")]
     ParseFmt {
@@ -105,9 +216,15 @@ pub enum Error {
         /// Error message.
         msg: String,
     },
-    /// A command ran failed.
-    #[error("one or more command evocations failed")]
-    CommandFailed,
+    /// One or more commands failed.
+    #[error("one or more command evocations failed{0}")]
+    CommandFailed(Failures),
+    /// A command template was empty.
+    #[error("a command needs at least an executable")]
+    EmptyCommand,
+    /// A pattern was not valid utf-8.
+    #[error("pattern `{}` is not valid utf-8", .0.display())]
+    NonUtf8Pattern(OsString),
     /// No capture group with given index exists for first match.
     #[error(
         "no capture group with index {idx} exists for \
@@ -152,9 +269,23 @@ pub enum Error {
     },
 }
 
+/// A pattern to group by, with the template to use for it's groups.
+#[derive(Debug)]
+struct Pattern<'a> {
+    /// Regex source.
+    pattern: &'a str,
+    /// Compiled regex.
+    regex: Regex,
+    /// Index of template to use.
+    template: usize,
+}
+
+/// A group of inputs.
 #[derive(Debug)]
 struct Group<'s> {
+    /// Captures of a match of the group, may be missing for remainder.
     captures: Option<Captures<'s>>,
+    /// Inputs of group.
     inputs: Vec<&'s OsStr>,
 }
 
@@ -170,133 +301,102 @@ impl ::file_suite_common::Run for Cli {
             strict,
             remainder,
             ignore_case,
+            match_on,
+            tag,
+            timeout,
+            retries,
+            fail_fast,
+            keep_going: _,
+            min_members,
+            max_members,
+            summary,
+            reverse,
+            pattern,
+            walk,
             command,
         } = self;
-        let [exe, command @ ..] = command.as_slice() else {
-            panic!("command should contain at least 1 element according to Parser impl")
-        };
 
-        let args = command
+        let extra_patterns = pattern
+            .patterns
             .iter()
-            .map(|arg| {
-                ::parse_fmt::parse_fmt(arg.as_bytes())
-                    .collect::<Result<SmallVec<[_; 3]>, _>>()
-                    .map_err(|err| {
-                        use ::std::fmt::Write;
-                        let arg = arg.display().to_string();
-                        let msg = match err.as_slice() {
-                            [] => String::new(),
-                            errors => {
-                                let mut out = String::new();
-                                for err in errors {
-                                    write!(out, "\n{err}").expect("write to string should succeed");
-                                }
-                                out
-                            }
-                        };
-                        Error::ParseFmt { arg, msg }
-                    })
-                    .and_then(|chunks| LookupChunk::from_chunks::<SmallVec<[_; 3]>, _>(chunks))
+            .map(|pattern| {
+                let [regex, command @ ..] = pattern.as_slice() else {
+                    unreachable!(
+                        "pattern should contain at least 1 element according to Parser impl"
+                    )
+                };
+                let regex = regex
+                    .to_str()
+                    .ok_or_else(|| Error::NonUtf8Pattern(regex.clone()))?;
+                Ok((regex, command))
             })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let pattern = regex;
-        let regex = ::regex::bytes::RegexBuilder::new(&pattern)
-            .case_insensitive(ignore_case)
-            .build()?;
-
-        let highest = regex.captures_len() - 1;
-        let groups = regex.capture_names().flatten().collect::<FxHashSet<_>>();
-
-        for chunk in args.iter().flatten().copied() {
-            match chunk {
-                LookupChunk::CaptureIdx(idx) | LookupChunk::CaptureIdxOpt(idx) => {
-                    if idx > highest {
-                        return Err(Error::UnknownGroupIdx {
-                            idx,
-                            pattern,
-                            highest,
-                        });
-                    }
-                }
-                LookupChunk::CaptureName(name) | LookupChunk::CaptureNameOpt(name) => {
-                    if !groups.contains(name) {
-                        return Err(Error::UnknownGroupName {
-                            name: name.to_owned(),
-                            pattern,
-                        });
-                    }
-                }
-                _ => {}
-            }
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if command.is_empty() && summary.is_none() {
+            return Err(Error::EmptyCommand);
         }
 
-        let input = ::std::io::stdin()
-            .lock()
-            .split(if read0 { b'\0' } else { b'\n' })
-            .collect::<Result<Vec<_>, ::std::io::Error>>()
-            .map_err(Error::InputIO)?;
+        let mut templates = Vec::from([CommandTemplate::parse(&command)?]);
+        let mut patterns = Vec::with_capacity(extra_patterns.len() + 1);
+        for (pattern, command) in
+            ::std::iter::once((regex.as_str(), &[] as &[OsString])).chain(extra_patterns)
+        {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()?;
+            let template = if command.is_empty() {
+                0
+            } else {
+                templates.push(CommandTemplate::parse(command)?);
+                templates.len() - 1
+            };
+            templates[template].validate(&regex, pattern)?;
+            patterns.push(Pattern {
+                pattern,
+                regex,
+                template,
+            });
+        }
 
-        let entries = if let Some(remainder) = &remainder {
-            input
-                .par_iter()
-                .filter_map(|haystack| {
-                    if !strict && haystack.is_empty() {
-                        return None;
-                    }
-                    Some(if let Some(caps) = regex.captures(haystack) {
-                        (
-                            if ignore_case {
-                                Cow::Owned(OsString::from_vec(::insensitive_buf::to_upper(
-                                    &caps[0],
-                                )))
-                            } else {
-                                Cow::Borrowed(OsStr::from_bytes(
-                                    caps.get(0)
-                                        .expect("capture group 0 should always exist")
-                                        .as_bytes(),
-                                ))
-                            },
-                            Some(caps),
-                            OsStr::from_bytes(haystack),
-                        )
-                    } else {
-                        (
-                            Cow::Borrowed(OsStr::from_bytes(remainder.as_bytes())),
-                            None::<Captures>,
-                            OsStr::from_bytes(haystack),
-                        )
-                    })
-                })
-                .collect::<Vec<_>>()
+        let input = if walk.roots.is_empty() {
+            ::std::io::stdin()
+                .lock()
+                .split(if read0 { b'\0' } else { b'\n' })
+                .collect::<Result<Vec<_>, ::std::io::Error>>()
+                .map_err(Error::InputIO)?
         } else {
-            input
-                .par_iter()
-                .filter_map(|haystack| {
-                    if !strict && haystack.is_empty() {
-                        return None;
-                    }
-                    regex.captures(haystack).map(|caps| {
-                        (
-                            if ignore_case {
-                                Cow::Owned(OsString::from_vec(::insensitive_buf::to_upper(
-                                    &caps[0],
-                                )))
-                            } else {
-                                Cow::Borrowed(OsStr::from_bytes(
-                                    caps.get(0)
-                                        .expect("capture group 0 should always exist")
-                                        .as_bytes(),
-                                ))
-                            },
-                            Some(caps),
-                            OsStr::from_bytes(haystack),
-                        )
-                    })
-                })
-                .collect::<Vec<_>>()
+            walk.walk()
         };
 
+        let entries = input
+            .par_iter()
+            .filter_map(|haystack| {
+                if !strict && haystack.is_empty() {
+                    return None;
+                }
+                let haystack_os = OsStr::from_bytes(haystack);
+                let Some((idx, caps)) = match_on.select(haystack).and_then(|part| {
+                    patterns.iter().enumerate().find_map(|(idx, pattern)| {
+                        pattern.regex.captures(part).map(|caps| (idx, caps))
+                    })
+                }) else {
+                    return remainder.as_deref().map(|remainder| {
+                        ((0, Cow::Borrowed(remainder)), None::<Captures>, haystack_os)
+                    });
+                };
+                let key = if ignore_case {
+                    Cow::Owned(OsString::from_vec(::insensitive_buf::to_upper(&caps[0])))
+                } else {
+                    Cow::Borrowed(OsStr::from_bytes(
+                        caps.get(0)
+                            .expect("capture group 0 should always exist")
+                            .as_bytes(),
+                    ))
+                };
+                Some(((idx, key), Some(caps), haystack_os))
+            })
+            .collect::<Vec<_>>();
+
         let mut map = ::hashbrown::HashMap::<_, Group>::new();
         for (key, captures, haystack) in entries {
             use ::hashbrown::hash_map::Entry::{Occupied, Vacant};
@@ -318,144 +418,57 @@ impl ::file_suite_common::Run for Cli {
             }
         }
 
+        map.retain(|_, group| (min_members..=max_members).contains(&group.inputs.len()));
+
+        if let Some(sort_by) = summary {
+            let mut summaries = map
+                .par_iter()
+                .map(|((_, key), group)| GroupSummary::new(key, &group.inputs))
+                .collect::<Vec<_>>();
+            GroupSummary::sort(&mut summaries, sort_by, reverse);
+            return GroupSummary::write_table(&summaries, stdout().lock()).map_err(Error::OutputIO);
+        }
+
         let commands = map
             .into_par_iter()
-            .map(|(key, value)| {
-                // do arg formatting here
-                let mut command = Command::new(&exe);
-
-                let mut buf = Vec::<u8>::new();
-                for arg in &args {
-                    buf.clear();
-                    for chunk in arg {
-                        match *chunk {
-                            LookupChunk::Text(os_str) => buf.extend_from_slice(os_str.as_bytes()),
-                            LookupChunk::CaptureIdx(idx) => {
-                                if value.captures.is_none() && idx == 0 {
-                                    buf.extend_from_slice(key.as_bytes());
-                                } else if let Some(captures) = &value.captures {
-                                    let r#match = captures.get(idx).ok_or_else(|| {
-                                        Error::MissingGroupIdx {
-                                            idx,
-                                            pattern: pattern.clone(),
-                                        }
-                                    })?;
-                                    buf.extend_from_slice(r#match.as_bytes());
-                                } else {
-                                    return Err(Error::MissingGroupIdx {
-                                        idx,
-                                        pattern: pattern.clone(),
-                                    });
-                                }
-                            }
-                            LookupChunk::CaptureName(name) => {
-                                let Some(captures) = &value.captures else {
-                                    return Err(Error::MissingGroupName {
-                                        name: name.to_owned(),
-                                        pattern: pattern.clone(),
-                                    });
-                                };
-                                let r#match =
-                                    captures.name(name).ok_or_else(|| Error::MissingGroupName {
-                                        name: name.to_owned(),
-                                        pattern: pattern.clone(),
-                                    })?;
-                                buf.extend_from_slice(r#match.as_bytes());
-                            }
-                            LookupChunk::CaptureIdxOpt(idx) => {
-                                let bytes = value
-                                    .captures
-                                    .as_ref()
-                                    .and_then(|captures| captures.get(idx))
-                                    .map(|m| m.as_bytes())
-                                    .unwrap_or(&[]);
-                                buf.extend_from_slice(bytes);
-                            }
-                            LookupChunk::CaptureNameOpt(name) => {
-                                let bytes = value
-                                    .captures
-                                    .as_ref()
-                                    .and_then(|captures| captures.name(name))
-                                    .map(|m| m.as_bytes())
-                                    .unwrap_or(&[]);
-                                buf.extend_from_slice(bytes);
-                            }
-                        }
-                    }
-                    command.arg(OsStr::from_bytes(&buf));
-                }
-
+            .map(|((idx, key), value)| {
+                let Pattern {
+                    pattern,
+                    regex,
+                    template,
+                } = &patterns[idx];
+                let mut command =
+                    templates[*template].build(&key, value.captures.as_ref(), pattern)?;
+                export_env(
+                    &mut command,
+                    regex,
+                    &key,
+                    value.captures.as_ref(),
+                    value.inputs.len(),
+                );
                 Ok((command, value.inputs, key))
             })
             .collect::<Result<Vec<_>, Error>>()?;
 
-        fn spawn(
-            sep: u8,
-            mut command: Command,
-            inputs: Vec<&OsStr>,
-            group: &OsStr,
-        ) -> Result<ExitStatus, ::std::io::Error> {
-            let mut child = command
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()?;
-            let mut stdin = child.stdin.take().expect("stdin pipe should exist");
-
-            for input in inputs {
-                stdin.write_all(input.as_bytes())?;
-                stdin.write_all(&[sep])?;
-            }
-
-            drop(stdin);
-
-            let result = child.wait_with_output()?;
-
-            if !result.stdout.is_empty() {
-                let mut stdout = stdout().lock();
-                writeln!(stdout, "group <{group}> stdout:", group = group.display())?;
-                stdout.write_all(&result.stdout)?;
-            }
-            if !result.stderr.is_empty() {
-                let mut stderr = stderr().lock();
-                writeln!(stderr, "group <{group}> stderr:", group = group.display())?;
-                stderr.write_all(&result.stderr)?;
-            }
-
-            Ok(result.status)
-        }
-
         let sep = if print0 { b'\0' } else { b'\n' };
-        let results = commands
+        let policy = Policy {
+            timeout,
+            retries,
+            fail_fast,
+            abort: AtomicBool::new(false),
+        };
+        let mut failures = commands
             .into_par_iter()
-            .map(|(command, inputs, group)| (spawn(sep, command, inputs, &group), group))
+            .filter_map(|(command, inputs, group)| {
+                policy.run(sep, tag, command, &inputs, &group).err()
+            })
             .collect::<Vec<_>>();
 
-        let mut failure = false;
-        for (result, group) in results {
-            match result {
-                Err(err) => {
-                    ::log::error!(
-                        "io error for group <{group}>\n{err}",
-                        group = group.display()
-                    );
-                    failure = true;
-                }
-                Ok(status) if !status.success() => {
-                    ::log::error!(
-                        "command from group <{group}> did not succeed, {status}",
-                        group = group.display()
-                    );
-                    failure = true;
-                }
-                _ => {}
-            }
-        }
-
-        if failure {
-            Err(Error::CommandFailed)
-        } else {
+        if failures.is_empty() {
             Ok(())
+        } else {
+            failures.sort_by(|a, b| a.group.cmp(&b.group));
+            Err(Error::CommandFailed(Failures(failures)))
         }
     }
 }
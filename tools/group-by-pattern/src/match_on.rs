@@ -0,0 +1,74 @@
+//! [MatchOn] implementation.
+
+use ::std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Component, Path},
+    str::FromStr,
+};
+
+/// Part of an input path to match patterns against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchOn {
+    /// The whole input.
+    #[default]
+    Path,
+    /// Final component of path.
+    Basename,
+    /// Path without final component.
+    Dirname,
+    /// Final component without extension.
+    Stem,
+    /// Extension of final component, without the dot.
+    Ext,
+    /// Component at index, negative indices count from the end.
+    Component(isize),
+}
+
+impl FromStr for MatchOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "path" => Self::Path,
+            "basename" => Self::Basename,
+            "dirname" => Self::Dirname,
+            "stem" => Self::Stem,
+            "ext" => Self::Ext,
+            other => {
+                let Some(idx) = other.strip_prefix("component=") else {
+                    return Err(format!(
+                        "expected one of path, basename, dirname, stem, ext or component=N, got `{other}`"
+                    ));
+                };
+                Self::Component(
+                    idx.parse()
+                        .map_err(|err| format!("could not parse component index `{idx}`, {err}"))?,
+                )
+            }
+        })
+    }
+}
+
+impl MatchOn {
+    /// Select the part of haystack to match against, if it exists.
+    pub fn select(self, haystack: &[u8]) -> Option<&[u8]> {
+        let path = Path::new(OsStr::from_bytes(haystack));
+        let part = match self {
+            MatchOn::Path => return Some(haystack),
+            MatchOn::Basename => path.file_name()?,
+            MatchOn::Dirname => path.parent()?.as_os_str(),
+            MatchOn::Stem => path.file_stem()?,
+            MatchOn::Ext => path.extension()?,
+            MatchOn::Component(idx) => {
+                let mut components = path.components().map(Component::as_os_str);
+                if let Ok(idx) = usize::try_from(idx) {
+                    components.nth(idx)?
+                } else {
+                    components.nth_back(idx.unsigned_abs() - 1)?
+                }
+            }
+        };
+        Some(part.as_bytes())
+    }
+}
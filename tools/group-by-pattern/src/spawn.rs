@@ -0,0 +1,315 @@
+//! Spawning and supervision of group commands.
+
+use ::std::{
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    io::{self, BufRead, BufReader, Read, Write, stderr, stdout},
+    os::unix::{ffi::OsStrExt, process::CommandExt},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ::rustix::process::{Pid, Signal, kill_process_group};
+
+/// How often supervised commands are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Reason for the failure of the command of a group.
+#[derive(Debug)]
+pub enum Failure {
+    /// Command could not be spawned or communicated with.
+    Io(io::Error),
+    /// Command exited unsuccessfully.
+    Status(ExitStatus),
+    /// Command ran for longer than the timeout.
+    TimedOut(Duration),
+    /// Command was killed or never started due to the failure of another group.
+    Cancelled,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Io(err) => write!(f, "io error, {err}"),
+            Failure::Status(status) => write!(f, "did not succeed, {status}"),
+            Failure::TimedOut(timeout) => write!(f, "timed out after {timeout:?}"),
+            Failure::Cancelled => write!(f, "cancelled due to failure of another group"),
+        }
+    }
+}
+
+/// A group for which the command failed.
+#[derive(Debug)]
+pub struct GroupFailure {
+    /// Group of command.
+    pub group: OsString,
+    /// How many times the command was started.
+    pub attempts: usize,
+    /// Why the last attempt failed.
+    pub failure: Failure,
+}
+
+impl Display for GroupFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            group,
+            attempts,
+            failure,
+        } = self;
+        write!(f, "group <{group}> {failure}", group = group.display())?;
+        if *attempts > 1 {
+            write!(f, ", after {attempts} attempts")?;
+        }
+        Ok(())
+    }
+}
+
+/// Failures of all failed groups, displayed as a list.
+#[derive(Debug)]
+pub struct Failures(pub Vec<GroupFailure>);
+
+impl Display for Failures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for failure in &self.0 {
+            write!(f, "\n  {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How commands are supervised.
+#[derive(Debug)]
+pub struct Policy {
+    /// Kill commands running for longer than this.
+    pub timeout: Option<Duration>,
+    /// How many times to retry failed commands.
+    pub retries: usize,
+    /// Cancel all other commands after the first failure.
+    pub fail_fast: bool,
+    /// Set when remaining commands should be cancelled.
+    pub abort: AtomicBool,
+}
+
+impl Policy {
+    /// Commands need to be supervised, and as such polled and put in their own process group.
+    const fn is_supervised(&self) -> bool {
+        self.timeout.is_some() || self.fail_fast
+    }
+
+    /// Run command for a group, retrying on failure.
+    ///
+    /// # Errors
+    /// If the last attempt failed, or the command was cancelled.
+    pub fn run(
+        &self,
+        sep: u8,
+        tag: bool,
+        mut command: Command,
+        inputs: &[&OsStr],
+        group: &OsStr,
+    ) -> Result<(), GroupFailure> {
+        if self.is_supervised() {
+            command.process_group(0);
+        }
+
+        let mut attempts = 0;
+        loop {
+            if self.abort.load(Ordering::Relaxed) {
+                return Err(GroupFailure {
+                    group: group.to_owned(),
+                    attempts,
+                    failure: Failure::Cancelled,
+                });
+            }
+
+            attempts += 1;
+            let failure = match spawn(sep, tag, self, &mut command, inputs, group) {
+                Ok(()) => return Ok(()),
+                Err(failure) => failure,
+            };
+
+            if attempts > self.retries || matches!(failure, Failure::Cancelled) {
+                if self.fail_fast {
+                    self.abort.store(true, Ordering::Relaxed);
+                }
+                return Err(GroupFailure {
+                    group: group.to_owned(),
+                    attempts,
+                    failure,
+                });
+            }
+
+            ::log::warn!(
+                "command from group <{group}> {failure}, retrying",
+                group = group.display()
+            );
+        }
+    }
+}
+
+/// Write inputs separated by sep to a writer.
+///
+/// # Errors
+/// If writing fails.
+fn write_inputs(mut w: impl Write, sep: u8, inputs: &[&OsStr]) -> io::Result<()> {
+    for input in inputs {
+        w.write_all(input.as_bytes())?;
+        w.write_all(&[sep])?;
+    }
+    Ok(())
+}
+
+/// Stream lines read from pipe to out, with every line prefixed by group and a tab.
+///
+/// # Errors
+/// If reading or writing fails.
+fn stream_tagged(pipe: impl Read, mut out: impl Write, group: &OsStr) -> io::Result<()> {
+    let mut reader = BufReader::new(pipe);
+    let mut line = Vec::from(group.as_bytes());
+    line.push(b'\t');
+    let prefix_len = line.len();
+
+    loop {
+        line.truncate(prefix_len);
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        out.write_all(&line)?;
+    }
+}
+
+/// Read output of a command, streaming it if tag is set, otherwise collecting it.
+///
+/// # Errors
+/// If reading or writing fails.
+fn read_output(
+    mut pipe: impl Read,
+    out: impl Write,
+    tag: bool,
+    group: &OsStr,
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if tag {
+        stream_tagged(pipe, out, group)?;
+    } else {
+        pipe.read_to_end(&mut buf)?;
+    }
+    Ok(buf)
+}
+
+/// Kill a child and everything in it's process group.
+///
+/// # Errors
+/// If the child cannot be killed.
+fn kill(child: &mut Child) -> io::Result<()> {
+    if kill_process_group(Pid::from_child(child), Signal::KILL).is_err() {
+        child.kill()?;
+    }
+    Ok(())
+}
+
+/// Wait for a child to exit, killing it if it times out or commands are aborted.
+///
+/// # Errors
+/// If the child is killed or waiting fails.
+fn wait(child: &mut Child, policy: &Policy) -> Result<ExitStatus, Failure> {
+    if !policy.is_supervised() {
+        return child.wait().map_err(Failure::Io);
+    }
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().map_err(Failure::Io)? {
+            return Ok(status);
+        }
+
+        let failure = if policy.abort.load(Ordering::Relaxed) {
+            Failure::Cancelled
+        } else if let Some(timeout) = policy.timeout.filter(|t| start.elapsed() >= *t) {
+            Failure::TimedOut(timeout)
+        } else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+
+        kill(child).map_err(Failure::Io)?;
+        child.wait().map_err(Failure::Io)?;
+        return Err(failure);
+    }
+}
+
+/// Spawn command for a group, passing inputs separated by sep on stdin.
+///
+/// If tag is set output of the command is streamed line by line prefixed by the group, otherwise
+/// it is printed as a block once the command exits.
+///
+/// # Errors
+/// If the command cannot be spawned, any io fails, or it does not succeed.
+///
+/// # Panics
+/// If the pipes of the spawned command are missing, or a pipe thread panics.
+fn spawn(
+    sep: u8,
+    tag: bool,
+    policy: &Policy,
+    command: &mut Command,
+    inputs: &[&OsStr],
+    group: &OsStr,
+) -> Result<(), Failure> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Failure::Io)?;
+    let child_stdin = child.stdin.take().expect("stdin pipe should exist");
+    let child_stdout = child.stdout.take().expect("stdout pipe should exist");
+    let child_stderr = child.stderr.take().expect("stderr pipe should exist");
+
+    let (status, written, out, err) = thread::scope(|s| {
+        let written = s.spawn(|| write_inputs(child_stdin, sep, inputs));
+        let out = s.spawn(|| read_output(child_stdout, &stdout(), tag, group));
+        let err = s.spawn(|| read_output(child_stderr, &stderr(), tag, group));
+
+        let status = wait(&mut child, policy);
+
+        (
+            status,
+            written.join().expect("stdin thread should not panic"),
+            out.join().expect("stdout thread should not panic"),
+            err.join().expect("stderr thread should not panic"),
+        )
+    });
+
+    let out = out.map_err(Failure::Io)?;
+    if !out.is_empty() {
+        let mut stdout = stdout().lock();
+        writeln!(stdout, "group <{group}> stdout:", group = group.display())
+            .map_err(Failure::Io)?;
+        stdout.write_all(&out).map_err(Failure::Io)?;
+    }
+
+    let err = err.map_err(Failure::Io)?;
+    if !err.is_empty() {
+        let mut stderr = stderr().lock();
+        writeln!(stderr, "group <{group}> stderr:", group = group.display())
+            .map_err(Failure::Io)?;
+        stderr.write_all(&err).map_err(Failure::Io)?;
+    }
+
+    let status = status?;
+    if !status.success() {
+        return Err(Failure::Status(status));
+    }
+
+    // Commands are free to not read their inputs.
+    match written {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        written => written.map_err(Failure::Io),
+    }
+}
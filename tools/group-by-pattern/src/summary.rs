@@ -0,0 +1,107 @@
+//! [GroupSummary] implementation.
+
+use ::std::{
+    ffi::OsStr,
+    fs,
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+};
+
+use ::bytesize::ByteSize;
+use ::clap::ValueEnum;
+
+/// Field to sort summary by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Sort by group key.
+    #[default]
+    Key,
+    /// Sort by amount of members.
+    Count,
+    /// Sort by total size of members.
+    Size,
+}
+
+/// Summary of a group.
+#[derive(Debug)]
+pub struct GroupSummary<'a> {
+    /// Key of group.
+    key: &'a OsStr,
+    /// Amount of members.
+    count: usize,
+    /// Total size of members which are paths, none if no member is an existing path.
+    size: Option<u64>,
+}
+
+impl<'a> GroupSummary<'a> {
+    /// Create a summary of a group, any inputs which are paths have their size summed.
+    pub fn new(key: &'a OsStr, inputs: &[&OsStr]) -> Self {
+        let size = inputs
+            .iter()
+            .filter_map(|input| fs::metadata(input).ok())
+            .map(|metadata| metadata.len())
+            .reduce(|a, b| a + b);
+        Self {
+            key,
+            count: inputs.len(),
+            size,
+        }
+    }
+
+    /// Sort summaries by field, ties are sorted by key.
+    pub fn sort(summaries: &mut [Self], sort_by: SortBy, reverse: bool) {
+        summaries.sort_by(|a, b| {
+            let ordering = match sort_by {
+                SortBy::Key => a.key.cmp(b.key),
+                SortBy::Count => a.count.cmp(&b.count).then_with(|| a.key.cmp(b.key)),
+                SortBy::Size => a.size.cmp(&b.size).then_with(|| a.key.cmp(b.key)),
+            };
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    /// Write summaries as a table.
+    ///
+    /// # Errors
+    /// If writing fails.
+    pub fn write_table(summaries: &[Self], mut w: impl Write) -> io::Result<()> {
+        let sizes = summaries
+            .iter()
+            .map(|summary| {
+                summary
+                    .size
+                    .map_or_else(|| String::from("-"), |size| ByteSize(size).to_string())
+            })
+            .collect::<Vec<_>>();
+
+        let count_width = summaries
+            .iter()
+            .map(|summary| summary.count.to_string().len())
+            .chain([5])
+            .max()
+            .unwrap_or_default();
+        let size_width = sizes
+            .iter()
+            .map(String::len)
+            .chain([4])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            w,
+            "{:>count_width$}  {:>size_width$}  GROUP",
+            "COUNT", "SIZE"
+        )?;
+        for (summary, size) in summaries.iter().zip(sizes) {
+            write!(w, "{:>count_width$}  {size:>size_width$}  ", summary.count)?;
+            w.write_all(summary.key.as_bytes())?;
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! [Walk] implementation.
+
+use ::std::{os::unix::ffi::OsStrExt, path::PathBuf};
+
+use ::clap::{Args, ValueEnum, ValueHint};
+use ::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use ::walkdir::{DirEntry, WalkDir};
+
+/// Type of walked entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FileType {
+    /// Regular files.
+    #[value(alias = "f")]
+    File,
+    /// Directories.
+    #[value(alias = "d")]
+    Dir,
+    /// Symbolic links.
+    #[value(alias = "l")]
+    Symlink,
+}
+
+impl FileType {
+    /// Check if entry is of type.
+    fn matches(self, entry: &DirEntry) -> bool {
+        let file_type = entry.file_type();
+        match self {
+            FileType::File => file_type.is_file(),
+            FileType::Dir => file_type.is_dir(),
+            FileType::Symlink => file_type.is_symlink(),
+        }
+    }
+}
+
+/// Directory walking configuration.
+#[derive(Debug, Args)]
+#[command(next_help_heading = "Walk")]
+pub struct Walk {
+    /// Walk directory for inputs instead of reading them from stdin, may be given multiple times.
+    #[arg(long = "walk", value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub roots: Vec<PathBuf>,
+
+    /// Minimum depth of walked entries.
+    ///
+    /// At 0 the directories walked are included, at 1 it's their contents.
+    #[arg(long, default_value_t = 1, requires = "roots")]
+    pub min_depth: usize,
+
+    /// Maximum depth of walked entries.
+    ///
+    /// To disable recursion specify 1.
+    #[arg(long, default_value_t = usize::MAX, requires = "roots")]
+    pub max_depth: usize,
+
+    /// Include hidden files and directories when walking.
+    #[arg(long, short = 'H', requires = "roots")]
+    pub hidden: bool,
+
+    /// Follow symbolic links when walking.
+    #[arg(long, short = 'L', requires = "roots")]
+    pub follow: bool,
+
+    /// Only include walked entries of given type, may be given multiple times.
+    #[arg(long = "type", short = 't', value_enum, requires = "roots")]
+    pub file_type: Vec<FileType>,
+}
+
+impl Walk {
+    /// Walk roots, collecting paths as inputs.
+    pub fn walk(&self) -> Vec<Vec<u8>> {
+        let Self {
+            roots,
+            min_depth,
+            max_depth,
+            hidden,
+            follow,
+            file_type,
+        } = self;
+
+        let is_hidden = |entry: &DirEntry| entry.file_name().as_bytes().starts_with(b".");
+
+        roots
+            .par_iter()
+            .flat_map_iter(|root| {
+                WalkDir::new(root)
+                    .min_depth(*min_depth)
+                    .max_depth(*max_depth)
+                    .follow_links(*follow)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_entry(|entry| *hidden || entry.depth() == 0 || !is_hidden(entry))
+                    .filter_map(|entry| {
+                        entry
+                            .inspect_err(|err| ::log::warn!("could not get dir entry, {err}"))
+                            .ok()
+                    })
+                    .filter(|entry| {
+                        file_type.is_empty() || file_type.iter().any(|ty| ty.matches(entry))
+                    })
+                    .map(|entry| entry.into_path().into_os_string().into_encoded_bytes())
+            })
+            .collect()
+    }
+}